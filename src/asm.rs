@@ -0,0 +1,119 @@
+//! Assembling and disassembling snippets without a loaded file.
+//!
+//! `Asm` keeps a single r2 session around and only changes `asm.arch` and
+//! `asm.bits` when a call asks for a different target, so assembling many
+//! snippets costs one round trip each.
+
+use crate::bin::Permissions;
+use crate::disasm::Instruction;
+use crate::util::{check_name, check_single_line, decode_hex};
+use crate::{Error, R2Pipe, R2PipeSpawnOptions, Result};
+
+/// Assembler and disassembler backed by an r2 session.
+pub struct Asm {
+    r2: R2Pipe,
+    arch: Option<String>,
+    bits: Option<u32>,
+}
+
+impl Asm {
+    /// Spawns a minimal r2 session, without analysis or a file on disk.
+    pub fn new() -> Result<Asm> {
+        let opts = R2PipeSpawnOptions {
+            args: vec!["-n"],
+            ..Default::default()
+        };
+        Ok(Asm::with_pipe(R2Pipe::spawn("malloc://512", Some(opts))?))
+    }
+
+    /// Reuses an existing session. Its `asm.arch` and `asm.bits` are
+    /// changed by the calls made through the returned `Asm`.
+    pub fn with_pipe(r2: R2Pipe) -> Asm {
+        Asm {
+            r2,
+            arch: None,
+            bits: None,
+        }
+    }
+
+    /// Gives back the underlying session.
+    pub fn into_inner(self) -> R2Pipe {
+        self.r2
+    }
+
+    /// Assembles `text` into machine code (`pa`). Instructions are
+    /// separated by newlines or `;`.
+    pub fn assemble(&mut self, arch: &str, bits: u32, text: &str) -> Result<Vec<u8>> {
+        self.set_target(arch, bits)?;
+        let text = text
+            .lines()
+            .map(str::trim)
+            .filter(|l| !l.is_empty())
+            .collect::<Vec<_>>()
+            .join(";");
+        let out = self.r2.call(&format!("pa {}", check_single_line(&text)?))?;
+        match decode_hex(&out) {
+            Some(bytes) if !bytes.is_empty() => Ok(bytes),
+            _ => Err(Error::CommandFailed(format!("cannot assemble {:?}", text))),
+        }
+    }
+
+    /// Disassembles `bytes` as if they were located at `addr`.
+    pub fn disassemble(
+        &mut self,
+        arch: &str,
+        bits: u32,
+        bytes: &[u8],
+        addr: u64,
+    ) -> Result<Vec<Instruction>> {
+        if bytes.is_empty() {
+            return Ok(Vec::new());
+        }
+        self.set_target(arch, bits)?;
+        let perm = Permissions {
+            read: true,
+            write: true,
+            exec: true,
+        };
+        let uri = format!("malloc://{}", bytes.len());
+        let file = self.r2.open_file(&uri, addr, perm)?;
+        let res = self
+            .r2
+            .write_bytes(addr, bytes)
+            .and_then(|_| self.r2.disasm_bytes(addr, bytes.len() as u64));
+        self.r2.close_file(file.fd)?;
+        res
+    }
+
+    fn set_target(&mut self, arch: &str, bits: u32) -> Result<()> {
+        if self.arch.as_deref() != Some(arch) {
+            self.arch = None;
+            self.r2.config_set("asm.arch", check_name(arch)?)?;
+            self.arch = Some(arch.to_string());
+        }
+        if self.bits != Some(bits) {
+            self.bits = None;
+            self.r2.config_set("asm.bits", bits)?;
+            self.bits = Some(bits);
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    #[cfg(not(windows))]
+    use super::Asm;
+
+    #[test]
+    #[cfg(not(windows))]
+    fn asm_test() {
+        let mut asm = Asm::new().unwrap();
+        let bytes = asm.assemble("x86", 64, "nop\nret").unwrap();
+        assert_eq!(bytes, [0x90, 0xc3]);
+        let insns = asm.disassemble("x86", 64, &bytes, 0x1000).unwrap();
+        assert_eq!(insns.len(), 2);
+        assert_eq!(insns[1].offset, 0x1001);
+        assert_eq!(insns[1].mnemonic(), "ret");
+    }
+}
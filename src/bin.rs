@@ -0,0 +1,221 @@
+//! Binary metadata from the `i` family of commands.
+//!
+//! Sections, segments, symbols, imports, exports, relocations, libraries,
+//! entry points and headers of the loaded binary as typed values.
+
+use crate::{R2Pipe, Result};
+
+use serde::Deserializer;
+use serde_derive::Deserialize;
+
+/// Access permissions of a section, segment or map.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub struct Permissions {
+    pub read: bool,
+    pub write: bool,
+    pub exec: bool,
+}
+
+impl Permissions {
+    /// Decodes r2's permission strings such as `-r-x` or `rw-`.
+    pub fn parse(perm: &str) -> Permissions {
+        Permissions {
+            read: perm.contains('r'),
+            write: perm.contains('w'),
+            exec: perm.contains('x'),
+        }
+    }
+}
+
+impl std::fmt::Display for Permissions {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let flag = |set, c| if set { c } else { '-' };
+        write!(
+            f,
+            "{}{}{}",
+            flag(self.read, 'r'),
+            flag(self.write, 'w'),
+            flag(self.exec, 'x')
+        )
+    }
+}
+
+pub(crate) fn deserialize_perm<'de, D>(
+    deserializer: D,
+) -> std::result::Result<Permissions, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    use serde::Deserialize;
+    Ok(Permissions::parse(&String::deserialize(deserializer)?))
+}
+
+/// A section (`iSj`) or segment (`iSSj`) of the binary.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub struct Section {
+    pub name: String,
+    #[serde(default)]
+    pub size: u64,
+    #[serde(default)]
+    pub vsize: u64,
+    pub paddr: u64,
+    pub vaddr: u64,
+    #[serde(default, deserialize_with = "deserialize_perm")]
+    pub perm: Permissions,
+}
+
+/// A symbol (`isj`) or export (`iEj`) of the binary.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub struct Symbol {
+    pub name: String,
+    /// Demangled name, when `bin.demangle` is enabled and applies.
+    #[serde(rename = "demname")]
+    pub demangled: Option<String>,
+    #[serde(default)]
+    pub realname: String,
+    /// Name of the flag r2 created for the symbol.
+    #[serde(default)]
+    pub flagname: String,
+    #[serde(default)]
+    pub ordinal: u64,
+    #[serde(default)]
+    pub bind: String,
+    #[serde(rename = "type", default)]
+    pub kind: String,
+    #[serde(default)]
+    pub size: u64,
+    #[serde(default)]
+    pub vaddr: u64,
+    #[serde(default)]
+    pub paddr: u64,
+    #[serde(default)]
+    pub is_imported: bool,
+}
+
+impl Symbol {
+    /// The demangled name if any, the raw name otherwise.
+    pub fn display_name(&self) -> &str {
+        self.demangled
+            .as_deref()
+            .filter(|d| !d.is_empty())
+            .unwrap_or(&self.name)
+    }
+}
+
+/// An imported symbol (`iij`).
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub struct Import {
+    pub name: String,
+    #[serde(default)]
+    pub libname: String,
+    #[serde(default)]
+    pub ordinal: u64,
+    #[serde(default)]
+    pub bind: String,
+    #[serde(rename = "type", default)]
+    pub kind: String,
+    /// Address of the PLT stub, if any.
+    pub plt: Option<u64>,
+}
+
+/// A relocation (`irj`).
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub struct Relocation {
+    #[serde(default)]
+    pub name: String,
+    #[serde(rename = "demname")]
+    pub demangled: Option<String>,
+    #[serde(rename = "type", default)]
+    pub kind: String,
+    pub vaddr: u64,
+    pub paddr: u64,
+    #[serde(default)]
+    pub is_ifunc: bool,
+}
+
+/// An entry point (`iej`).
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub struct EntryPoint {
+    pub vaddr: u64,
+    pub paddr: u64,
+    /// Kind of entry point, e.g. `program`, `init` or `fini`.
+    #[serde(rename = "type", default)]
+    pub kind: String,
+}
+
+/// A header field (`ihj`).
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub struct HeaderField {
+    #[serde(default)]
+    pub name: String,
+    pub vaddr: Option<u64>,
+    #[serde(default)]
+    pub paddr: u64,
+    #[serde(default)]
+    pub comment: String,
+    #[serde(default)]
+    pub format: String,
+}
+
+impl R2Pipe {
+    pub fn sections(&mut self) -> Result<Vec<Section>> {
+        self.cmdj_or_empty("iSj")
+    }
+
+    pub fn segments(&mut self) -> Result<Vec<Section>> {
+        self.cmdj_or_empty("iSSj")
+    }
+
+    pub fn symbols(&mut self) -> Result<Vec<Symbol>> {
+        self.cmdj_or_empty("isj")
+    }
+
+    pub fn imports(&mut self) -> Result<Vec<Import>> {
+        self.cmdj_or_empty("iij")
+    }
+
+    pub fn exports(&mut self) -> Result<Vec<Symbol>> {
+        self.cmdj_or_empty("iEj")
+    }
+
+    pub fn relocations(&mut self) -> Result<Vec<Relocation>> {
+        self.cmdj_or_empty("irj")
+    }
+
+    /// Names of the libraries the binary links against (`ilj`).
+    pub fn libraries(&mut self) -> Result<Vec<String>> {
+        self.cmdj_or_empty("ilj")
+    }
+
+    pub fn entrypoints(&mut self) -> Result<Vec<EntryPoint>> {
+        self.cmdj_or_empty("iej")
+    }
+
+    pub fn headers(&mut self) -> Result<Vec<HeaderField>> {
+        self.cmdj_or_empty("ihj")
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{Permissions, Section};
+
+    #[test]
+    fn section_from_isj() {
+        let s: Section = serde_json::from_str(
+            r#"{"name":".text","size":4096,"vsize":4096,"perm":"-r-x",
+                "paddr":4096,"vaddr":4198400}"#,
+        )
+        .unwrap();
+        assert_eq!(
+            s.perm,
+            Permissions {
+                read: true,
+                write: false,
+                exec: true
+            }
+        );
+        assert_eq!(s.perm.to_string(), "r-x");
+        assert_eq!(s.vaddr, 0x401000);
+    }
+}
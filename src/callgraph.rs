@@ -0,0 +1,232 @@
+//! Whole-program call graph, built from `aflj` and `axffj`.
+//!
+//! Functions are identified by their start address. Reachability queries
+//! start from the entry points (`iej`) and exports (`iEj`) of the binary,
+//! which is what dead-code and attack-surface analyses usually need.
+
+use crate::{R2Pipe, Result};
+
+use std::collections::{BTreeMap, BTreeSet};
+
+use serde_derive::Deserialize;
+
+/// A function node of the call graph.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FunctionNode {
+    pub addr: u64,
+    pub name: String,
+    pub size: u64,
+}
+
+/// How the callee of an edge was reached.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum CallKind {
+    /// A direct call instruction, or a tail jump.
+    Direct,
+    /// An indirect call or jump whose target was resolved by r2.
+    Indirect,
+    /// The address of the callee is taken, e.g. to pass it as a callback.
+    Reference,
+}
+
+/// A call from one function to another.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct CallEdge {
+    /// Start address of the calling function.
+    pub from: u64,
+    /// Start address of the called function.
+    pub to: u64,
+    /// Address of the instruction performing the call.
+    pub at: u64,
+    pub kind: CallKind,
+}
+
+/// Call graph of all the functions known to r2.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct CallGraph {
+    /// Functions indexed by start address.
+    pub functions: BTreeMap<u64, FunctionNode>,
+    pub edges: Vec<CallEdge>,
+    /// Program entry points that are function starts.
+    pub entrypoints: Vec<u64>,
+    /// Exported symbols that are function starts.
+    pub exports: Vec<u64>,
+}
+
+#[derive(Deserialize)]
+struct AxfEntry {
+    #[serde(rename = "type", default)]
+    kind: String,
+    at: u64,
+    #[serde(rename = "ref")]
+    to: u64,
+}
+
+impl CallGraph {
+    pub fn callees(&self, addr: u64) -> Vec<u64> {
+        let set: BTreeSet<u64> = self
+            .edges
+            .iter()
+            .filter(|e| e.from == addr)
+            .map(|e| e.to)
+            .collect();
+        set.into_iter().collect()
+    }
+
+    pub fn callers(&self, addr: u64) -> Vec<u64> {
+        let set: BTreeSet<u64> = self
+            .edges
+            .iter()
+            .filter(|e| e.to == addr)
+            .map(|e| e.from)
+            .collect();
+        set.into_iter().collect()
+    }
+
+    /// Returns the functions reachable from `roots`, roots included.
+    pub fn reachable_from(&self, roots: &[u64]) -> BTreeSet<u64> {
+        let mut succs: BTreeMap<u64, Vec<u64>> = BTreeMap::new();
+        for e in &self.edges {
+            succs.entry(e.from).or_default().push(e.to);
+        }
+        let mut seen = BTreeSet::new();
+        let mut work: Vec<u64> = roots
+            .iter()
+            .copied()
+            .filter(|r| self.functions.contains_key(r))
+            .collect();
+        while let Some(f) = work.pop() {
+            if seen.insert(f) {
+                work.extend(succs.get(&f).into_iter().flatten().copied());
+            }
+        }
+        seen
+    }
+
+    /// Returns the functions reachable from the program entry points.
+    pub fn reachable_from_entry(&self) -> BTreeSet<u64> {
+        self.reachable_from(&self.entrypoints)
+    }
+
+    /// Returns the functions reachable from the exported symbols.
+    pub fn reachable_from_exports(&self) -> BTreeSet<u64> {
+        self.reachable_from(&self.exports)
+    }
+
+    /// Returns the functions reachable neither from the entry points nor
+    /// from the exports.
+    pub fn unreachable(&self) -> BTreeSet<u64> {
+        let roots: Vec<u64> = self
+            .entrypoints
+            .iter()
+            .chain(&self.exports)
+            .copied()
+            .collect();
+        let reachable = self.reachable_from(&roots);
+        self.functions
+            .keys()
+            .filter(|f| !reachable.contains(f))
+            .copied()
+            .collect()
+    }
+}
+
+impl R2Pipe {
+    /// Builds the call graph of all the functions found by the analysis.
+    /// Run `aa` or a similar command beforehand.
+    pub fn call_graph(&mut self) -> Result<CallGraph> {
+        let mut graph = CallGraph {
+            functions: self
+                .functions()?
+                .into_iter()
+                .map(|f| {
+                    let node = FunctionNode {
+                        addr: f.addr,
+                        name: f.name,
+                        size: f.size,
+                    };
+                    (f.addr, node)
+                })
+                .collect(),
+            ..Default::default()
+        };
+
+        let addrs: Vec<u64> = graph.functions.keys().copied().collect();
+        let cmds: Vec<String> = addrs.iter().map(|a| format!("axffj @ {:#x}", a)).collect();
+        let refs: Vec<Vec<AxfEntry>> = self.cmdj_batch(&cmds)?;
+        for (from, refs) in addrs.into_iter().zip(refs) {
+            for r in refs {
+                let kind = match r.kind.as_str() {
+                    // CODE references to another function are tail jumps
+                    "CALL" | "CODE" => CallKind::Direct,
+                    "ICOD" => CallKind::Indirect,
+                    "DATA" => CallKind::Reference,
+                    _ => continue,
+                };
+                // jumps inside a function target no other function entry,
+                // except for loops back to its own entry; recursive calls
+                // are kept
+                let is_loop = r.kind == "CODE" && r.to == from;
+                if is_loop || !graph.functions.contains_key(&r.to) {
+                    continue;
+                }
+                graph.edges.push(CallEdge {
+                    from,
+                    to: r.to,
+                    at: r.at,
+                    kind,
+                });
+            }
+        }
+
+        graph.entrypoints = self
+            .entrypoints()?
+            .into_iter()
+            .map(|e| e.vaddr)
+            .filter(|a| graph.functions.contains_key(a))
+            .collect();
+        graph.exports = self
+            .exports()?
+            .into_iter()
+            .map(|e| e.vaddr)
+            .filter(|a| graph.functions.contains_key(a))
+            .collect();
+        Ok(graph)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{CallEdge, CallGraph, CallKind, FunctionNode};
+
+    #[test]
+    fn reachability() {
+        let mut graph = CallGraph::default();
+        for addr in 1..=5 {
+            graph.functions.insert(
+                addr,
+                FunctionNode {
+                    addr,
+                    name: format!("fcn.{}", addr),
+                    size: 1,
+                },
+            );
+        }
+        for (from, to) in [(1, 2), (2, 3), (3, 2), (3, 3), (4, 5)] {
+            graph.edges.push(CallEdge {
+                from,
+                to,
+                at: from,
+                kind: CallKind::Direct,
+            });
+        }
+        graph.entrypoints = vec![1];
+        graph.exports = vec![5];
+        assert_eq!(graph.reachable_from_entry().len(), 3);
+        assert_eq!(graph.unreachable().into_iter().collect::<Vec<_>>(), vec![4]);
+        assert_eq!(graph.callers(2), vec![1, 3]);
+        // recursive calls are self edges
+        assert_eq!(graph.callers(3), vec![2, 3]);
+        assert_eq!(graph.callees(3), vec![2, 3]);
+    }
+}
@@ -0,0 +1,292 @@
+//! Classes of C++, Objective-C, Java or Swift binaries.
+//!
+//! Two sources are available: the classes described by the binary itself
+//! (`icj`), and the classes recovered by the analysis (`acllj`), which also
+//! know the offsets of base classes and the vtables (`avj`) of the class.
+
+use crate::util::check_name;
+use crate::{R2Pipe, Result};
+
+use serde_json::Value;
+
+/// A base class.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BaseClass {
+    pub name: String,
+    /// Offset of the base inside the derived class, when known.
+    pub offset: Option<u64>,
+}
+
+/// A method of a class.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Method {
+    pub name: String,
+    pub addr: u64,
+    /// Offset of the method in the vtable, for virtual methods.
+    pub vtable_offset: Option<u64>,
+}
+
+/// A field of a class.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ClassField {
+    pub name: String,
+    pub addr: u64,
+    /// Type of the field, when known.
+    pub kind: String,
+}
+
+/// An entry of a vtable.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct VTableEntry {
+    /// Address of the slot.
+    pub offset: u64,
+    pub name: String,
+}
+
+/// A virtual method table.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct VTable {
+    pub addr: u64,
+    /// Offset of the vtable pointer inside the class.
+    pub offset: u64,
+    pub entries: Vec<VTableEntry>,
+}
+
+/// A class and its members.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Class {
+    pub name: String,
+    pub addr: Option<u64>,
+    /// Language of the class, e.g. `c++` or `objc`, when known.
+    pub lang: String,
+    pub bases: Vec<BaseClass>,
+    pub methods: Vec<Method>,
+    pub fields: Vec<ClassField>,
+    pub vtables: Vec<VTable>,
+}
+
+fn str_of(v: &Value, key: &str) -> String {
+    v[key].as_str().unwrap_or_default().to_string()
+}
+
+fn items<'a>(v: &'a Value, key: &str) -> impl Iterator<Item = &'a Value> {
+    v[key].as_array().into_iter().flatten()
+}
+
+impl Class {
+    /// Builds a class from an entry of `icj`.
+    fn from_icj(v: &Value) -> Option<Class> {
+        // older versions report a single base as a string
+        let bases = match &v["super"] {
+            Value::String(name) => vec![name.as_str()],
+            Value::Array(names) => names.iter().filter_map(Value::as_str).collect(),
+            _ => Vec::new(),
+        };
+        Some(Class {
+            name: v["classname"].as_str()?.to_string(),
+            addr: v["addr"].as_u64(),
+            lang: str_of(v, "lang"),
+            bases: bases
+                .into_iter()
+                .map(|name| BaseClass {
+                    name: name.to_string(),
+                    offset: None,
+                })
+                .collect(),
+            methods: items(v, "methods")
+                .map(|m| Method {
+                    name: str_of(m, "name"),
+                    addr: m["addr"].as_u64().unwrap_or(0),
+                    vtable_offset: None,
+                })
+                .collect(),
+            fields: items(v, "fields")
+                .map(|f| ClassField {
+                    name: str_of(f, "name"),
+                    addr: f["addr"].as_u64().unwrap_or(0),
+                    kind: str_of(f, "type"),
+                })
+                .collect(),
+            vtables: Vec::new(),
+        })
+    }
+
+    /// Builds a class from an entry of `acllj`, looking up the entries of
+    /// its vtables in `vtables`.
+    fn from_acllj(v: &Value, vtables: &[VTable]) -> Option<Class> {
+        Some(Class {
+            name: v["name"].as_str()?.to_string(),
+            addr: None,
+            lang: String::new(),
+            bases: items(v, "bases")
+                .map(|b| BaseClass {
+                    name: str_of(b, "name"),
+                    offset: b["offset"].as_u64(),
+                })
+                .collect(),
+            methods: items(v, "methods")
+                .map(|m| Method {
+                    name: str_of(m, "name"),
+                    addr: m["addr"].as_u64().unwrap_or(0),
+                    vtable_offset: m["vtable_offset"].as_u64(),
+                })
+                .collect(),
+            fields: Vec::new(),
+            vtables: items(v, "vtables")
+                .filter_map(|t| {
+                    let addr = t["addr"].as_u64()?;
+                    Some(VTable {
+                        addr,
+                        offset: t["offset"].as_u64().unwrap_or(0),
+                        entries: vtables
+                            .iter()
+                            .find(|t| t.addr == addr)
+                            .map(|t| t.entries.clone())
+                            .unwrap_or_default(),
+                    })
+                })
+                .collect(),
+        })
+    }
+}
+
+impl VTable {
+    /// Builds a vtable from an entry of `avj`.
+    fn from_avj(v: &Value) -> Option<VTable> {
+        Some(VTable {
+            addr: v["offset"].as_u64()?,
+            offset: 0,
+            entries: items(v, "methods")
+                .filter_map(|m| {
+                    Some(VTableEntry {
+                        offset: m["offset"].as_u64()?,
+                        name: str_of(m, "name"),
+                    })
+                })
+                .collect(),
+        })
+    }
+}
+
+impl R2Pipe {
+    /// Lists the classes described by the binary (`icj`).
+    pub fn classes(&mut self) -> Result<Vec<Class>> {
+        let list: Vec<Value> = self.cmdj_or_empty("icj")?;
+        let mut res: Vec<Class> = list.iter().filter_map(Class::from_icj).collect();
+        // names are only mangled when `bin.demangle` is off, demangle them
+        // all in a single batch
+        let mut mangled: Vec<String> = res
+            .iter()
+            .flat_map(|c| &c.methods)
+            .map(|m| m.name.clone())
+            .filter(|name| name.starts_with("_Z") && check_name(name).is_ok())
+            .collect();
+        mangled.sort_unstable();
+        mangled.dedup();
+        if mangled.is_empty() {
+            return Ok(res);
+        }
+        let cmds: Vec<String> = mangled
+            .iter()
+            .map(|name| format!("iD cxx {}", name))
+            .collect();
+        let demangled = self.cmd_batch(&cmds)?;
+        for m in res.iter_mut().flat_map(|c| &mut c.methods) {
+            if let Ok(i) = mangled.binary_search(&m.name) {
+                let name = demangled[i].trim();
+                if !name.is_empty() {
+                    m.name = name.to_string();
+                }
+            }
+        }
+        Ok(res)
+    }
+
+    /// Lists the classes recovered by the analysis, with their vtables
+    /// (`acllj`).
+    pub fn analysis_classes(&mut self) -> Result<Vec<Class>> {
+        let list: Vec<Value> = self.cmdj_or_empty("acllj")?;
+        let vtables = self.vtables()?;
+        Ok(list
+            .iter()
+            .filter_map(|v| Class::from_acllj(v, &vtables))
+            .collect())
+    }
+
+    /// Returns the class named `name` recovered by the analysis, if any.
+    pub fn analysis_class(&mut self, name: &str) -> Result<Option<Class>> {
+        Ok(self
+            .analysis_classes()?
+            .into_iter()
+            .find(|c| c.name == name))
+    }
+
+    /// Lists the vtables found in the data sections (`avj`).
+    pub fn vtables(&mut self) -> Result<Vec<VTable>> {
+        let list: Vec<Value> = self.cmdj_or_empty("avj")?;
+        Ok(list.iter().filter_map(VTable::from_avj).collect())
+    }
+
+    /// Demangles a symbol name of the given language, e.g. `cxx`, `objc`,
+    /// `swift` or `java` (`iD`). Names that cannot be demangled are
+    /// returned unchanged.
+    pub fn demangle(&mut self, lang: &str, name: &str) -> Result<String> {
+        let out = self.cmd(&format!("iD {} {}", check_name(lang)?, check_name(name)?))?;
+        let out = out.trim();
+        Ok(if out.is_empty() { name } else { out }.to_string())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{Class, VTable};
+    use crate::R2Pipe;
+    use serde_json::json;
+
+    #[test]
+    fn classes_from_json() {
+        let icj = json!({"classname": "Derived", "addr": 4096, "lang": "c++",
+                         "super": "Base", "methods": [{"name": "Derived::run", "addr": 8192}]});
+        let class = Class::from_icj(&icj).unwrap();
+        assert_eq!(class.bases[0].name, "Base");
+        assert_eq!(class.methods[0].addr, 8192);
+
+        let avj = json!({"offset": 12288, "methods": [{"offset": 12288, "name": "Derived::run"}]});
+        let vtables = [VTable::from_avj(&avj).unwrap()];
+        let acllj = json!({"name": "Derived",
+                           "bases": [{"id": "0", "name": "Base", "offset": 0}],
+                           "vtables": [{"id": "0", "addr": 12288, "offset": 0}],
+                           "methods": [{"name": "Derived::run", "addr": 8192, "vtable_offset": 0}]});
+        let class = Class::from_acllj(&acllj, &vtables).unwrap();
+        assert_eq!(class.bases[0].offset, Some(0));
+        assert_eq!(class.methods[0].vtable_offset, Some(0));
+        assert_eq!(class.vtables[0].entries[0].name, "Derived::run");
+    }
+
+    #[test]
+    fn mangled_methods() {
+        let mut calls = 0;
+        let mut r2 = R2Pipe::from_fn(move |cmd| {
+            calls += 1;
+            assert!(calls <= 2, "one round-trip per listing");
+            match cmd {
+                "icj" => json!([{"classname": "Derived", "methods": [
+                    {"name": "_ZN7Derived3runEv", "addr": 8192},
+                    {"name": "Derived::stop", "addr": 8208},
+                    {"name": "_ZN7Derived4initEv", "addr": 8224}]}])
+                .to_string(),
+                "iD cxx _ZN7Derived3runEv;?e --;iD cxx _ZN7Derived4initEv;?e --" => {
+                    "Derived::run()\n--\n\n--\n".to_string()
+                }
+                _ => panic!("unexpected command {}", cmd),
+            }
+        });
+        let classes = r2.classes().unwrap();
+        let names: Vec<&str> = classes[0].methods.iter().map(|m| m.name.as_str()).collect();
+        // names r2 cannot demangle are kept
+        assert_eq!(
+            names,
+            ["Derived::run()", "Derived::stop", "_ZN7Derived4initEv"]
+        );
+    }
+}
@@ -0,0 +1,192 @@
+//! Typed access to the configuration variables (`e`).
+//!
+//! `with_config` applies temporary overrides around a closure and restores
+//! the previous values afterwards, whether the closure succeeded or not.
+
+use crate::util::{check_name, check_single_line};
+use crate::{Error, R2Pipe, Result};
+
+use std::fmt::Display;
+
+use serde::{Deserialize, Deserializer};
+use serde_derive::Deserialize;
+use serde_json::Value;
+
+/// Types a configuration value can be read as.
+pub trait ConfigValue: Sized {
+    fn from_config(value: &str) -> Option<Self>;
+}
+
+impl ConfigValue for String {
+    fn from_config(value: &str) -> Option<String> {
+        Some(value.to_string())
+    }
+}
+
+impl ConfigValue for bool {
+    fn from_config(value: &str) -> Option<bool> {
+        match value {
+            "true" | "1" => Some(true),
+            "false" | "0" => Some(false),
+            _ => None,
+        }
+    }
+}
+
+macro_rules! config_int {
+    ($($t:ty),*) => {
+        $(impl ConfigValue for $t {
+            fn from_config(value: &str) -> Option<$t> {
+                match value.strip_prefix("0x") {
+                    Some(hex) => <$t>::from_str_radix(hex, 16).ok(),
+                    None => value.parse().ok(),
+                }
+            }
+        })*
+    };
+}
+
+config_int!(u8, u16, u32, u64, usize, i8, i16, i32, i64, isize);
+
+fn deserialize_text<'de, D>(deserializer: D) -> std::result::Result<String, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    Ok(match Value::deserialize(deserializer)? {
+        Value::String(s) => s,
+        Value::Null => String::new(),
+        other => other.to_string(),
+    })
+}
+
+/// A configuration variable, as listed by `e??j`.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub struct ConfigVar {
+    pub name: String,
+    #[serde(default, deserialize_with = "deserialize_text")]
+    pub value: String,
+    /// Type of the value, e.g. `bool`, `int` or `str`.
+    #[serde(rename = "type", default)]
+    pub kind: String,
+    #[serde(rename = "desc", default)]
+    pub description: String,
+    #[serde(rename = "ro", default)]
+    pub read_only: bool,
+    /// Accepted values, when restricted to a fixed set.
+    #[serde(default)]
+    pub options: Vec<String>,
+}
+
+impl R2Pipe {
+    /// Reads the configuration variable `key`.
+    pub fn config_get<T: ConfigValue>(&mut self, key: &str) -> Result<T> {
+        let out = self.cmd(&format!("e {}", check_name(key)?))?;
+        let value = out.trim();
+        if out.is_empty() {
+            return Err(Error::CommandFailed(format!("unknown config key {}", key)));
+        }
+        T::from_config(value).ok_or_else(|| {
+            Error::CommandFailed(format!("unexpected value {:?} for {}", value, key))
+        })
+    }
+
+    /// Sets the configuration variable `key` to `value`.
+    pub fn config_set(&mut self, key: &str, value: impl Display) -> Result<()> {
+        let value = value.to_string();
+        self.call(&format!(
+            "e {}={}",
+            check_name(key)?,
+            check_single_line(&value)?
+        ))?;
+        Ok(())
+    }
+
+    /// Lists all the configuration variables with their descriptions.
+    pub fn config_list(&mut self) -> Result<Vec<ConfigVar>> {
+        self.cmdj_or_empty("e??j")
+    }
+
+    /// Runs `f` with the given configuration overrides, restoring the
+    /// previous values afterwards even if `f` fails.
+    pub fn with_config<T, F>(&mut self, overrides: &[(&str, &str)], f: F) -> Result<T>
+    where
+        F: FnOnce(&mut R2Pipe) -> Result<T>,
+    {
+        let mut saved = Vec::with_capacity(overrides.len());
+        let mut res = Ok(());
+        for &(key, value) in overrides {
+            match self.config_get::<String>(key) {
+                Ok(old) => saved.push((key, old)),
+                Err(e) => {
+                    res = Err(e);
+                    break;
+                }
+            }
+            if let Err(e) = self.config_set(key, value) {
+                res = Err(e);
+                break;
+            }
+        }
+        let mut res = res.and_then(|_| f(self));
+        // keep restoring after a failure, reporting the first error
+        for (key, old) in saved.into_iter().rev() {
+            if let Err(e) = self.config_set(key, old) {
+                if res.is_ok() {
+                    res = Err(e);
+                }
+            }
+        }
+        res
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::ConfigValue;
+    use crate::{Error, R2Pipe};
+    use std::cell::RefCell;
+    use std::collections::BTreeMap;
+    use std::rc::Rc;
+
+    #[test]
+    fn config_values() {
+        assert_eq!(u64::from_config("0xffffffffffffffff"), Some(u64::MAX));
+        assert_eq!(i32::from_config("64"), Some(64));
+        assert_eq!(bool::from_config("false"), Some(false));
+        assert_eq!(bool::from_config("x86"), None);
+    }
+
+    #[test]
+    fn overrides_restored_on_error() {
+        let vars = Rc::new(RefCell::new(BTreeMap::new()));
+        vars.borrow_mut().insert("a".to_string(), "1".to_string());
+        vars.borrow_mut().insert("b".to_string(), "2".to_string());
+        let store = vars.clone();
+        let mut r2 = R2Pipe::from_fallible_fn(move |cmd| {
+            let mut store = store.borrow_mut();
+            if let Some((key, value)) = cmd.strip_prefix("\"\"e ").and_then(|c| c.split_once('=')) {
+                // restoring `b` fails
+                if key == "b" && value == "2" {
+                    return Err(Error::CommandFailed("read only".to_string()));
+                }
+                store.insert(key.to_string(), value.to_string());
+                return Ok(String::new());
+            }
+            let key = cmd.strip_prefix("e ").unwrap();
+            Ok(format!("{}\n", store[key]))
+        });
+        let res: crate::Result<()> = r2.with_config(&[("a", "10"), ("b", "20")], |r2| {
+            assert_eq!(r2.config_get::<u64>("a")?, 10);
+            Err(Error::CommandFailed("closure".to_string()))
+        });
+        // `b` is restored first and fails, `a` is still restored and the
+        // error of the closure wins
+        assert!(matches!(res, Err(Error::CommandFailed(m)) if m == "closure"));
+        assert_eq!(vars.borrow()["a"], "1");
+
+        // a failed restore is reported when the closure succeeded
+        vars.borrow_mut().insert("b".to_string(), "2".to_string());
+        let res = r2.with_config(&[("b", "30")], |_| Ok(()));
+        assert!(matches!(res, Err(Error::CommandFailed(m)) if m == "read only"));
+    }
+}
@@ -0,0 +1,227 @@
+//! Debugger control for sessions started with `r2 -d`.
+//!
+//! A `Debugger` borrows the pipe and wraps the `d` family of commands:
+//! execution control, breakpoints, registers, memory maps and backtraces.
+
+use crate::bin::{deserialize_perm, Permissions};
+use crate::util::check_name;
+use crate::{Error, R2Pipe, Result};
+
+use std::collections::BTreeMap;
+
+use serde_derive::Deserialize;
+use serde_json::Value;
+
+/// A breakpoint as listed by `dbj`.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub struct Breakpoint {
+    pub addr: u64,
+    #[serde(default)]
+    pub size: u64,
+    #[serde(default, deserialize_with = "deserialize_perm")]
+    pub perm: Permissions,
+    #[serde(default)]
+    pub hw: bool,
+    #[serde(default)]
+    pub enabled: bool,
+}
+
+/// A memory map of the debugged process, as listed by `dmj`.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub struct MemoryMap {
+    #[serde(default)]
+    pub name: String,
+    #[serde(default)]
+    pub file: String,
+    pub addr: u64,
+    pub addr_end: u64,
+    #[serde(default, deserialize_with = "deserialize_perm")]
+    pub perm: Permissions,
+}
+
+/// A frame of the backtrace, as listed by `dbtj`.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub struct Frame {
+    pub pc: u64,
+    #[serde(default)]
+    pub sp: u64,
+    #[serde(default)]
+    pub frame_size: u64,
+    #[serde(rename = "fname")]
+    pub function: Option<String>,
+}
+
+/// Why the debugged process stopped.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StopReason {
+    Breakpoint,
+    Step,
+    /// The process received the given signal.
+    Signal(i32),
+    /// The process has exited.
+    Exited,
+    /// Any other reason, holding r2's reason code.
+    Other(i64),
+}
+
+/// State of the debugged process after it stopped, from `dij`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StopInfo {
+    pub reason: StopReason,
+    /// Address the stop refers to, e.g. the breakpoint that was hit.
+    pub addr: u64,
+    pub pid: Option<i64>,
+}
+
+// r2's RDebugReasonType values
+const REASON_DEAD: i64 = -1;
+const REASON_BREAKPOINT: i64 = 3;
+const REASON_STEP: i64 = 7;
+const REASON_EXIT_PID: i64 = 17;
+
+impl StopInfo {
+    fn from_json(info: &Value) -> StopInfo {
+        let code = info["stopreason"].as_i64().unwrap_or(0);
+        let signum = info["signum"].as_i64().unwrap_or(0);
+        let reason = if info["inbp"].as_bool().unwrap_or(false) || code == REASON_BREAKPOINT {
+            StopReason::Breakpoint
+        } else if code == REASON_DEAD || code == REASON_EXIT_PID {
+            StopReason::Exited
+        } else if code == REASON_STEP {
+            StopReason::Step
+        } else if signum > 0 {
+            StopReason::Signal(signum as i32)
+        } else {
+            StopReason::Other(code)
+        };
+        StopInfo {
+            reason,
+            addr: info["addr"].as_u64().unwrap_or(0),
+            pid: info["pid"].as_i64(),
+        }
+    }
+}
+
+/// Handle to drive the debugger of an `R2Pipe` session.
+pub struct Debugger<'a> {
+    r2: &'a mut R2Pipe,
+}
+
+impl R2Pipe {
+    /// Returns a handle to control the debugger. The session must have
+    /// been started in debug mode, e.g. spawned with the `-d` argument.
+    pub fn debugger(&mut self) -> Debugger<'_> {
+        Debugger { r2: self }
+    }
+}
+
+impl Debugger<'_> {
+    /// Continues execution until the next stop (`dc`).
+    pub fn continue_(&mut self) -> Result<StopInfo> {
+        self.r2.cmd("dc")?;
+        self.stop_info()
+    }
+
+    /// Continues execution until `addr` is reached (`dcu`).
+    pub fn continue_until(&mut self, addr: u64) -> Result<StopInfo> {
+        self.r2.cmd(&format!("dcu {:#x}", addr))?;
+        self.stop_info()
+    }
+
+    /// Steps one instruction (`ds`).
+    pub fn step(&mut self) -> Result<StopInfo> {
+        self.r2.cmd("ds")?;
+        self.stop_info()
+    }
+
+    /// Steps one instruction, stepping over calls (`dso`).
+    pub fn step_over(&mut self) -> Result<StopInfo> {
+        self.r2.cmd("dso")?;
+        self.stop_info()
+    }
+
+    /// Reports why and where the process last stopped (`dij`).
+    pub fn stop_info(&mut self) -> Result<StopInfo> {
+        Ok(StopInfo::from_json(&self.r2.cmdj("dij")?))
+    }
+
+    pub fn add_breakpoint(&mut self, addr: u64) -> Result<()> {
+        self.r2.cmd(&format!("db {:#x}", addr))?;
+        Ok(())
+    }
+
+    pub fn remove_breakpoint(&mut self, addr: u64) -> Result<()> {
+        self.r2.cmd(&format!("db- {:#x}", addr))?;
+        Ok(())
+    }
+
+    pub fn breakpoints(&mut self) -> Result<Vec<Breakpoint>> {
+        self.r2.cmdj_or_empty("dbj")
+    }
+
+    /// Values of the general purpose registers (`drj`).
+    pub fn registers(&mut self) -> Result<BTreeMap<String, u64>> {
+        Ok(serde_json::from_value(self.r2.cmdj("drj")?)?)
+    }
+
+    /// Current value of the program counter.
+    pub fn pc(&mut self) -> Result<u64> {
+        let name = self.r2.cmd("drn PC")?;
+        self.register(name.trim())?
+            .ok_or_else(|| Error::CommandFailed("cannot read the program counter".to_string()))
+    }
+
+    /// Value of a single register, `None` if it does not exist.
+    pub fn register(&mut self, name: &str) -> Result<Option<u64>> {
+        Ok(self.registers()?.remove(name))
+    }
+
+    pub fn set_register(&mut self, name: &str, value: u64) -> Result<()> {
+        self.r2
+            .cmd(&format!("dr {}={:#x}", check_name(name)?, value))?;
+        Ok(())
+    }
+
+    /// Memory maps of the debugged process (`dmj`).
+    pub fn maps(&mut self) -> Result<Vec<MemoryMap>> {
+        self.r2.cmdj_or_empty("dmj")
+    }
+
+    /// Backtrace of the current thread (`dbtj`).
+    pub fn backtrace(&mut self) -> Result<Vec<Frame>> {
+        self.r2.cmdj_or_empty("dbtj")
+    }
+}
+
+#[cfg(test)]
+mod test {
+    #[cfg(not(windows))]
+    use super::StopReason;
+    #[cfg(not(windows))]
+    use crate::{R2Pipe, R2PipeSpawnOptions};
+
+    #[test]
+    #[cfg(not(windows))]
+    fn debug_test() {
+        let opts = R2PipeSpawnOptions {
+            args: vec!["-d"],
+            ..Default::default()
+        };
+        let mut pipe = R2Pipe::spawn("/bin/true", Some(opts)).unwrap();
+        let entry = pipe.cmd("?v entry0").unwrap();
+        let entry = u64::from_str_radix(entry.trim().trim_start_matches("0x"), 16).unwrap();
+
+        let mut dbg = pipe.debugger();
+        dbg.add_breakpoint(entry).unwrap();
+        assert!(dbg.breakpoints().unwrap().iter().any(|b| b.addr == entry));
+        let stop = dbg.continue_().unwrap();
+        assert_eq!(stop.reason, StopReason::Breakpoint);
+        assert_eq!(dbg.pc().unwrap(), entry);
+        dbg.remove_breakpoint(entry).unwrap();
+        assert!(dbg.breakpoints().unwrap().is_empty());
+        dbg.step().unwrap();
+        assert_ne!(dbg.pc().unwrap(), entry);
+        assert!(!dbg.maps().unwrap().is_empty());
+        pipe.close();
+    }
+}
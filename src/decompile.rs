@@ -0,0 +1,134 @@
+//! Decompiled pseudo-code of functions.
+//!
+//! r2's own `pdc` is always available but only produces text. When the
+//! r2ghidra plugin is loaded, `pdgj` is used instead, whose offset
+//! annotations allow mapping every line of code back to addresses.
+
+use crate::{Error, R2Pipe, Result};
+
+use serde_derive::Deserialize;
+
+/// Decompiler producing the pseudo-code.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Decompiler {
+    /// r2's builtin pseudo-decompiler (`pdc`).
+    Pdc,
+    /// The r2ghidra plugin (`pdgj`).
+    Ghidra,
+}
+
+/// A range of the code generated from the instruction at `addr`.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub struct Annotation {
+    /// Byte offsets of the range in the code.
+    pub start: usize,
+    pub end: usize,
+    #[serde(rename = "offset")]
+    pub addr: u64,
+}
+
+/// Pseudo-code of a function.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Decompilation {
+    pub decompiler: Decompiler,
+    pub code: String,
+    /// Offset annotations, empty for `Decompiler::Pdc`.
+    pub annotations: Vec<Annotation>,
+}
+
+impl Decompilation {
+    /// Returns, for each line of the code, the addresses it was generated
+    /// from, in ascending order.
+    pub fn line_addrs(&self) -> Vec<Vec<u64>> {
+        let starts: Vec<usize> = std::iter::once(0)
+            .chain(self.code.match_indices('\n').map(|(i, _)| i + 1))
+            .collect();
+        let mut res = vec![Vec::new(); self.code.lines().count()];
+        for a in &self.annotations {
+            let line = starts.partition_point(|&s| s <= a.start).saturating_sub(1);
+            if let Some(addrs) = res.get_mut(line) {
+                addrs.push(a.addr);
+            }
+        }
+        for addrs in &mut res {
+            addrs.sort_unstable();
+            addrs.dedup();
+        }
+        res
+    }
+
+    /// Returns the index of the first line generated from `addr`.
+    pub fn line_of(&self, addr: u64) -> Option<usize> {
+        self.line_addrs().iter().position(|a| a.contains(&addr))
+    }
+}
+
+impl R2Pipe {
+    /// Whether the r2ghidra decompiler is loaded (`Lc`).
+    pub fn has_ghidra(&mut self) -> Result<bool> {
+        Ok(self.cmd("Lc")?.contains("r2ghidra"))
+    }
+
+    /// Decompiles the function containing `addr` with r2ghidra when it is
+    /// loaded, or with `pdc` otherwise.
+    pub fn decompile(&mut self, addr: u64) -> Result<Decompilation> {
+        let decompiler = if self.has_ghidra()? {
+            Decompiler::Ghidra
+        } else {
+            Decompiler::Pdc
+        };
+        self.decompile_with(addr, decompiler)
+    }
+
+    /// Decompiles the function containing `addr` with `decompiler`.
+    pub fn decompile_with(&mut self, addr: u64, decompiler: Decompiler) -> Result<Decompilation> {
+        let (code, annotations) = match decompiler {
+            Decompiler::Pdc => (self.cmd(&format!("pdc @ {:#x}", addr))?, Vec::new()),
+            Decompiler::Ghidra => {
+                let out = self.cmdj(&format!("pdgj @ {:#x}", addr))?;
+                let code = out["code"].as_str().unwrap_or_default().to_string();
+                // other annotations describe syntax highlighting, names, ...
+                let annotations = out["annotations"]
+                    .as_array()
+                    .into_iter()
+                    .flatten()
+                    .filter(|a| a["type"] == "offset")
+                    .filter_map(|a| serde_json::from_value(a.clone()).ok())
+                    .collect();
+                (code, annotations)
+            }
+        };
+        if code.trim().is_empty() {
+            return Err(Error::CommandFailed(format!(
+                "cannot decompile function at {:#x}",
+                addr
+            )));
+        }
+        Ok(Decompilation {
+            decompiler,
+            code,
+            annotations,
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{Annotation, Decompilation, Decompiler};
+
+    #[test]
+    fn line_addrs() {
+        let ann = |start, end, addr| Annotation { start, end, addr };
+        let dec = Decompilation {
+            decompiler: Decompiler::Ghidra,
+            code: "int main(void) {\n    puts(\"hi\");\n    return 0;\n}\n".to_string(),
+            annotations: vec![ann(0, 3, 0x1000), ann(21, 25, 0x1008), ann(19, 31, 0x1004)],
+        };
+        let lines = dec.line_addrs();
+        assert_eq!(lines.len(), 4);
+        assert_eq!(lines[0], [0x1000]);
+        assert_eq!(lines[1], [0x1004, 0x1008]);
+        assert!(lines[2].is_empty());
+        assert_eq!(dec.line_of(0x1008), Some(1));
+    }
+}
@@ -0,0 +1,220 @@
+//! Function level diffing of two binaries opened in separate sessions.
+//!
+//! Functions are paired in three passes, each one only considering the
+//! functions left unpaired by the previous ones and only pairing functions
+//! whose key is unique on both sides:
+//!
+//! 1. by name, ignoring names r2 derives from the address (`fcn.*`),
+//! 2. by the hash of their bytes, to follow renamed functions,
+//! 3. by graph shape: size, number of basic blocks and edges.
+
+use crate::functions::Function;
+use crate::hash::HashAlgorithm;
+use crate::{R2Pipe, Result};
+
+use std::collections::BTreeMap;
+
+/// How two functions were paired.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum MatchKind {
+    Name,
+    Hash,
+    Graph,
+}
+
+/// A function present in both binaries.
+#[derive(Debug, Clone, PartialEq)]
+pub struct FunctionDiff {
+    pub old: Function,
+    pub new: Function,
+    pub matched_by: MatchKind,
+    /// `1.0` for identical bytes, lower the more the size and the shape of
+    /// the control flow graph differ.
+    pub similarity: f64,
+}
+
+/// Differences between the functions of two binaries.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct BinaryDiff {
+    /// Functions only found in the new binary.
+    pub added: Vec<Function>,
+    /// Functions only found in the old binary.
+    pub removed: Vec<Function>,
+    pub changed: Vec<FunctionDiff>,
+    pub unchanged: Vec<FunctionDiff>,
+}
+
+struct Fingerprint {
+    function: Function,
+    hash: String,
+}
+
+fn is_generated_name(name: &str) -> bool {
+    ["fcn.", "sub.", "loc."].iter().any(|p| name.starts_with(p))
+}
+
+fn ratio(a: u64, b: u64) -> f64 {
+    if a == b {
+        1.0
+    } else {
+        a.min(b) as f64 / a.max(b) as f64
+    }
+}
+
+fn similarity(old: &Fingerprint, new: &Fingerprint) -> f64 {
+    if old.hash == new.hash {
+        return 1.0;
+    }
+    let (a, b) = (&old.function, &new.function);
+    let graph = (ratio(a.nbbs, b.nbbs) + ratio(a.edges, b.edges)) / 2.0;
+    // identical sizes and shapes can still hide different instructions
+    ((graph + ratio(a.size, b.size)) / 2.0).min(0.99)
+}
+
+/// Maps each key to the index of the only entry having it, `None` if
+/// several entries share it.
+fn unique_keys<K, F>(list: &[Fingerprint], key: &F) -> BTreeMap<K, Option<usize>>
+where
+    K: Ord,
+    F: Fn(&Fingerprint) -> Option<K>,
+{
+    let mut res = BTreeMap::new();
+    for (i, f) in list.iter().enumerate() {
+        if let Some(k) = key(f) {
+            res.entry(k).and_modify(|e| *e = None).or_insert(Some(i));
+        }
+    }
+    res
+}
+
+/// Pairs the functions whose key is unique on both sides, removing them
+/// from `old` and `new`.
+fn pair_by<K, F>(
+    old: &mut Vec<Fingerprint>,
+    new: &mut Vec<Fingerprint>,
+    kind: MatchKind,
+    key: F,
+) -> Vec<(Fingerprint, Fingerprint, MatchKind)>
+where
+    K: Ord,
+    F: Fn(&Fingerprint) -> Option<K>,
+{
+    let mut new_keys = unique_keys(new, &key);
+    let pairs: Vec<(usize, usize)> = unique_keys(old, &key)
+        .into_iter()
+        .filter_map(|(k, i)| Some((i?, new_keys.remove(&k)??)))
+        .collect();
+
+    let mut old_slots: Vec<Option<Fingerprint>> = old.drain(..).map(Some).collect();
+    let mut new_slots: Vec<Option<Fingerprint>> = new.drain(..).map(Some).collect();
+    let mut res = Vec::with_capacity(pairs.len());
+    for (i, j) in pairs {
+        if let (Some(o), Some(n)) = (old_slots[i].take(), new_slots[j].take()) {
+            res.push((o, n, kind));
+        }
+    }
+    old.extend(old_slots.into_iter().flatten());
+    new.extend(new_slots.into_iter().flatten());
+    res
+}
+
+fn diff_fingerprints(mut old: Vec<Fingerprint>, mut new: Vec<Fingerprint>) -> BinaryDiff {
+    let mut pairs = pair_by(&mut old, &mut new, MatchKind::Name, |f| {
+        let name = &f.function.name;
+        (!is_generated_name(name)).then(|| name.clone())
+    });
+    pairs.extend(pair_by(&mut old, &mut new, MatchKind::Hash, |f| {
+        (f.function.size > 0).then(|| f.hash.clone())
+    }));
+    pairs.extend(pair_by(&mut old, &mut new, MatchKind::Graph, |f| {
+        let f = &f.function;
+        Some((f.size, f.nbbs, f.edges))
+    }));
+
+    let mut res = BinaryDiff::default();
+    for (o, n, kind) in pairs {
+        let diff = FunctionDiff {
+            similarity: similarity(&o, &n),
+            old: o.function,
+            new: n.function,
+            matched_by: kind,
+        };
+        if diff.similarity < 1.0 {
+            res.changed.push(diff);
+        } else {
+            res.unchanged.push(diff);
+        }
+    }
+    res.changed.sort_by_key(|d| d.old.addr);
+    res.unchanged.sort_by_key(|d| d.old.addr);
+    res.removed = old.into_iter().map(|f| f.function).collect();
+    res.removed.sort_by_key(|f| f.addr);
+    res.added = new.into_iter().map(|f| f.function).collect();
+    res.added.sort_by_key(|f| f.addr);
+    res
+}
+
+impl R2Pipe {
+    /// Compares the functions analyzed in this session, the old binary,
+    /// with the ones analyzed in `new`.
+    pub fn diff_functions(&mut self, new: &mut R2Pipe) -> Result<BinaryDiff> {
+        Ok(diff_fingerprints(self.fingerprints()?, new.fingerprints()?))
+    }
+
+    fn fingerprints(&mut self) -> Result<Vec<Fingerprint>> {
+        let mut res = Vec::new();
+        for function in self.functions()? {
+            // `ph` falls back to the block size when given a length of 0
+            let hash = if function.size == 0 {
+                String::new()
+            } else {
+                self.hash(&HashAlgorithm::Sha256, function.addr, function.size)?
+                    .value
+            };
+            res.push(Fingerprint { function, hash });
+        }
+        Ok(res)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{diff_fingerprints, Fingerprint, MatchKind};
+    use serde_json::json;
+
+    fn fp(addr: u64, name: &str, size: u64, nbbs: u64, hash: &str) -> Fingerprint {
+        let function = serde_json::from_value(json!({
+            "offset": addr, "name": name, "size": size, "nbbs": nbbs, "edges": nbbs
+        }))
+        .unwrap();
+        Fingerprint {
+            function,
+            hash: hash.to_string(),
+        }
+    }
+
+    #[test]
+    fn diff_test() {
+        let old = vec![
+            fp(0x10, "main", 40, 3, "a"),
+            fp(0x40, "fcn.00000040", 16, 1, "b"),
+            fp(0x50, "fcn.00000050", 64, 5, "c"),
+            fp(0x90, "gone", 8, 1, "d"),
+        ];
+        let new = vec![
+            fp(0x10, "main", 48, 4, "a2"),
+            fp(0x48, "fcn.00000048", 16, 1, "b"),
+            fp(0x58, "fcn.00000058", 64, 5, "c2"),
+            fp(0xa0, "fresh", 12, 2, "e"),
+        ];
+        let diff = diff_fingerprints(old, new);
+        assert_eq!(diff.unchanged.len(), 1);
+        assert_eq!(diff.unchanged[0].new.addr, 0x48);
+        assert_eq!(diff.unchanged[0].matched_by, MatchKind::Hash);
+        let changed: Vec<_> = diff.changed.iter().map(|d| d.matched_by).collect();
+        assert_eq!(changed, [MatchKind::Name, MatchKind::Graph]);
+        assert!(diff.changed[0].similarity < 1.0);
+        assert_eq!(diff.removed[0].name, "gone");
+        assert_eq!(diff.added[0].name, "fresh");
+    }
+}
@@ -0,0 +1,180 @@
+//! Typed disassembly on top of `pdj` and `pDj`.
+//!
+//! Large requests are split into pages so that a single response never
+//! has to hold the whole listing.
+
+use crate::util::deserialize_hex;
+use crate::{Error, R2Pipe, Result};
+
+use serde_derive::{Deserialize, Serialize};
+
+/// Maximum number of instructions requested with a single `pdj`.
+const PAGE_INSTRUCTIONS: u64 = 512;
+/// Maximum number of bytes disassembled with a single `pDj`.
+const PAGE_BYTES: u64 = 4096;
+
+/// A reference from an instruction to another address.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Reference {
+    pub addr: u64,
+    /// Kind of reference as reported by r2, e.g. `CODE`, `CALL` or `DATA`.
+    #[serde(rename = "type", default)]
+    pub kind: String,
+}
+
+/// A single disassembled instruction, as reported by `pdj`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Instruction {
+    pub offset: u64,
+    #[serde(default)]
+    pub size: u64,
+    #[serde(default, deserialize_with = "deserialize_hex")]
+    pub bytes: Vec<u8>,
+    /// Plain instruction text, e.g. `mov eax, 1`.
+    #[serde(default)]
+    pub opcode: String,
+    /// Instruction text with flags and variables substituted.
+    #[serde(default)]
+    pub disasm: String,
+    /// Instruction type, e.g. `mov`, `call`, `cjmp` or `invalid`.
+    #[serde(rename = "type", default)]
+    pub kind: String,
+    pub jump: Option<u64>,
+    pub fail: Option<u64>,
+    #[serde(default)]
+    pub refs: Vec<Reference>,
+    #[serde(default)]
+    pub esil: String,
+    pub fcn_addr: Option<u64>,
+}
+
+impl Instruction {
+    /// The mnemonic of the instruction, e.g. `mov`.
+    pub fn mnemonic(&self) -> &str {
+        self.opcode.split_whitespace().next().unwrap_or("")
+    }
+
+    /// The comma-separated operands of the instruction.
+    pub fn operands(&self) -> Vec<&str> {
+        let ops = self
+            .opcode
+            .trim()
+            .split_once(char::is_whitespace)
+            .map(|(_, ops)| ops.trim())
+            .unwrap_or("");
+        if ops.is_empty() {
+            return Vec::new();
+        }
+        let mut res = Vec::new();
+        let (mut depth, mut start) = (0i32, 0);
+        for (i, c) in ops.char_indices() {
+            match c {
+                '[' | '(' | '{' => depth += 1,
+                ']' | ')' | '}' => depth -= 1,
+                ',' if depth == 0 => {
+                    res.push(ops[start..i].trim());
+                    start = i + 1;
+                }
+                _ => {}
+            }
+        }
+        res.push(ops[start..].trim());
+        res
+    }
+
+    /// Whether r2 failed to decode the instruction.
+    pub fn is_invalid(&self) -> bool {
+        self.kind == "invalid"
+    }
+
+    /// Address of the instruction immediately following this one.
+    pub fn next(&self) -> u64 {
+        self.offset.wrapping_add(self.size)
+    }
+}
+
+#[derive(Deserialize)]
+struct Block {
+    addr: u64,
+    size: u64,
+}
+
+impl R2Pipe {
+    /// Disassembles `count` instructions starting at `addr`.
+    pub fn disasm_at(&mut self, addr: u64, count: u64) -> Result<Vec<Instruction>> {
+        let mut res = Vec::new();
+        let mut cur = addr;
+        while (res.len() as u64) < count {
+            let n = (count - res.len() as u64).min(PAGE_INSTRUCTIONS);
+            let page: Vec<Instruction> = self.cmdj_or_empty(&format!("pdj {} @ {:#x}", n, cur))?;
+            let next = page
+                .last()
+                .filter(|last| last.size > 0)
+                .map(|last| last.next());
+            res.extend(page);
+            match next {
+                Some(next) => cur = next,
+                None => break,
+            }
+        }
+        res.truncate(count as usize);
+        Ok(res)
+    }
+
+    /// Disassembles the instructions found in the `len` bytes at `addr`.
+    pub fn disasm_bytes(&mut self, addr: u64, len: u64) -> Result<Vec<Instruction>> {
+        let end = addr.saturating_add(len);
+        let mut res = Vec::new();
+        let mut cur = addr;
+        while cur < end {
+            let n = (end - cur).min(PAGE_BYTES);
+            let page: Vec<Instruction> = self.cmdj_or_empty(&format!("pDj {} @ {:#x}", n, cur))?;
+            let next = page
+                .last()
+                .filter(|last| last.size > 0)
+                .map(|last| last.next());
+            res.extend(page);
+            match next {
+                Some(next) => cur = next,
+                None => break,
+            }
+        }
+        Ok(res)
+    }
+
+    /// Disassembles the function containing `addr`, one basic block at a
+    /// time, ordered by address.
+    pub fn disasm_function(&mut self, addr: u64) -> Result<Vec<Instruction>> {
+        let mut blocks: Vec<Block> = self.cmdj_or_empty(&format!("afbj @ {:#x}", addr))?;
+        if blocks.is_empty() {
+            return Err(Error::CommandFailed(format!("no function at {:#x}", addr)));
+        }
+        blocks.sort_by_key(|b| b.addr);
+        let mut res = Vec::new();
+        for b in blocks {
+            res.extend(self.disasm_bytes(b.addr, b.size)?);
+        }
+        Ok(res)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::Instruction;
+
+    #[test]
+    fn instruction_from_pdj() {
+        let ins: Instruction = serde_json::from_str(
+            r#"{"offset":4096,"esil":"0x10,rbp,-,[8],rax,=","refptr":0,"size":4,
+                "opcode":"mov rax, qword [rbp - 0x10]","disasm":"mov rax, qword [var_10h]",
+                "bytes":"488b45f0","family":"cpu","type":"mov","reloc":false,
+                "type_num":9,"type2_num":0}"#,
+        )
+        .unwrap();
+        assert_eq!(ins.bytes, vec![0x48, 0x8b, 0x45, 0xf0]);
+        assert_eq!(ins.mnemonic(), "mov");
+        assert_eq!(ins.operands(), vec!["rax", "qword [rbp - 0x10]"]);
+        assert_eq!(ins.next(), 4100);
+        assert_eq!(ins.jump, None);
+    }
+}
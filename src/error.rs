@@ -41,6 +41,14 @@ pub enum Error {
     #[error("Send channel data error")]
     ChannelSendError(#[from] SendError<String>),
 
+    /// An argument cannot be safely passed to radare2.
+    #[error("Invalid argument: {0}")]
+    InvalidArgument(String),
+
+    /// radare2 reported an error or produced unusable output for a command.
+    #[error("Command failed: {0}")]
+    CommandFailed(String),
+
     /// Error loading radare2 shared library.
     #[error("Shared library error: {0}")]
     SharedLibraryError(#[from] libloading::Error),
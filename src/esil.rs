@@ -0,0 +1,202 @@
+//! ESIL emulation on top of the `ae` family of commands.
+//!
+//! An `Esil` handle initializes the VM (`aei`) and its stack (`aeim`) and
+//! exposes register access, stepping and emulated memory as typed calls.
+
+use crate::util::{check_name, parse_u64};
+use crate::{Error, R2Pipe, Result};
+
+use serde_json::Value;
+use std::collections::BTreeMap;
+
+/// A condition that stopped the emulation.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Trap {
+    /// Program counter when the trap was detected.
+    pub addr: u64,
+    /// Trap message reported by r2, empty when the emulation just could not
+    /// make progress, e.g. because of an invalid instruction.
+    pub message: String,
+}
+
+/// Handle to the ESIL VM of an `R2Pipe` session.
+pub struct Esil<'a> {
+    r2: &'a mut R2Pipe,
+    /// Name of the program counter register, resolved once.
+    pc: String,
+}
+
+impl R2Pipe {
+    /// Initializes the ESIL VM and its stack, with the program counter set
+    /// to the current seek, and returns a handle to drive it.
+    pub fn esil(&mut self) -> Result<Esil<'_>> {
+        self.cmd("aei")?;
+        self.cmd("aeim")?;
+        self.cmd("aeip")?;
+        let pc = self.cmd("arn PC")?.trim().to_string();
+        check_name(&pc)?;
+        Ok(Esil { r2: self, pc })
+    }
+}
+
+fn parse_pc(out: &str) -> Result<u64> {
+    parse_u64(out)
+        .ok_or_else(|| Error::CommandFailed("cannot read the program counter".to_string()))
+}
+
+impl Esil<'_> {
+    /// Values of the emulated registers (`aerj`).
+    pub fn registers(&mut self) -> Result<BTreeMap<String, u64>> {
+        Ok(serde_json::from_value(self.r2.cmdj("aerj")?)?)
+    }
+
+    /// Value of a single register, `None` if it does not exist.
+    pub fn register(&mut self, name: &str) -> Result<Option<u64>> {
+        Ok(self.registers()?.remove(name))
+    }
+
+    pub fn set_register(&mut self, name: &str, value: u64) -> Result<()> {
+        self.r2
+            .cmd(&format!("aer {}={:#x}", check_name(name)?, value))?;
+        Ok(())
+    }
+
+    /// Current value of the emulated program counter.
+    pub fn pc(&mut self) -> Result<u64> {
+        let out = self.r2.cmd(&format!("aer {}", self.pc))?;
+        parse_pc(&out)
+    }
+
+    pub fn set_pc(&mut self, addr: u64) -> Result<()> {
+        self.r2.cmd(&format!("aepc {:#x}", addr))?;
+        Ok(())
+    }
+
+    /// Emulates a single instruction. Trap, illegal and invalid
+    /// instructions are reported instead of being emulated.
+    pub fn step(&mut self) -> Result<Option<Trap>> {
+        let pc = self.pc()?;
+        let ops: Vec<Value> = self.r2.cmdj_or_empty(&format!("aoj 1 @ {:#x}", pc))?;
+        let op = ops.first().unwrap_or(&Value::Null);
+        let kind = op["type"].as_str().unwrap_or("invalid");
+        if matches!(kind, "trap" | "ill" | "invalid") {
+            return Ok(Some(Trap {
+                addr: pc,
+                message: format!("{} instruction", kind),
+            }));
+        }
+        // step and read the new program counter in a single round-trip
+        let out = self
+            .r2
+            .cmd_batch(&["aes".to_string(), format!("aer {}", self.pc)])?;
+        // `jmp $` legitimately keeps the program counter in place
+        if parse_pc(&out[1])? == pc && op["jump"].as_u64() != Some(pc) {
+            return Ok(Some(Trap {
+                addr: pc,
+                message: out[0].trim().to_string(),
+            }));
+        }
+        Ok(None)
+    }
+
+    /// Emulates up to `count` instructions, stopping at the first trap.
+    pub fn steps(&mut self, count: u64) -> Result<Option<Trap>> {
+        for _ in 0..count {
+            if let Some(trap) = self.step()? {
+                return Ok(Some(trap));
+            }
+        }
+        Ok(None)
+    }
+
+    /// Emulates until the program counter reaches `addr` (`aesu`).
+    pub fn step_until(&mut self, addr: u64) -> Result<Option<Trap>> {
+        let out = self.r2.cmd(&format!("aesu {:#x}", addr))?;
+        let pc = self.pc()?;
+        Ok(if pc == addr {
+            None
+        } else {
+            Some(Trap {
+                addr: pc,
+                message: out.trim().to_string(),
+            })
+        })
+    }
+
+    /// Reads `len` bytes of emulated memory at `addr`.
+    pub fn read_memory(&mut self, addr: u64, len: u64) -> Result<Vec<u8>> {
+        self.r2.read_bytes(addr, len)
+    }
+
+    /// Writes `data` into emulated memory at `addr`.
+    pub fn write_memory(&mut self, addr: u64, data: &[u8]) -> Result<()> {
+        self.r2.write_bytes(addr, data)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::R2Pipe;
+    use serde_json::json;
+    use std::cell::Cell;
+    use std::rc::Rc;
+
+    /// Emulates a program made of `nop` at 0, `jmp $` at 1 and an illegal
+    /// instruction at 0x10, counting the `aes` commands and the round-trips
+    /// received.
+    fn fake_vm(start: u64) -> (R2Pipe, Rc<Cell<u64>>, Rc<Cell<u64>>) {
+        let (steps, trips) = (Rc::new(Cell::new(0)), Rc::new(Cell::new(0)));
+        let (count, calls) = (steps.clone(), trips.clone());
+        let mut pc = start;
+        let mut run = move |cmd: &str| {
+            let op = |addr| match addr {
+                0 => json!([{"type": "nop", "size": 1}]),
+                1 => json!([{"type": "jmp", "size": 2, "jump": 1}]),
+                _ => json!([{"type": "ill", "size": 1}]),
+            };
+            match cmd {
+                "aei" | "aeim" | "aeip" => String::new(),
+                "arn PC" => "rip\n".to_string(),
+                "aer rip" => format!("{:#x}\n", pc),
+                "?e --" => "--\n".to_string(),
+                "aes" => {
+                    count.set(count.get() + 1);
+                    pc = match pc {
+                        0 | 1 => 1,
+                        other => other,
+                    };
+                    String::new()
+                }
+                _ => {
+                    let addr = cmd.strip_prefix("aoj 1 @ 0x").expect(cmd);
+                    op(u64::from_str_radix(addr, 16).unwrap()).to_string()
+                }
+            }
+        };
+        let r2 = R2Pipe::from_fn(move |cmd| {
+            calls.set(calls.get() + 1);
+            cmd.split(';').map(&mut run).collect()
+        });
+        (r2, steps, trips)
+    }
+
+    #[test]
+    fn traps() {
+        // a jump to itself makes no progress but is not a trap
+        let (mut r2, steps, trips) = fake_vm(0);
+        let mut esil = r2.esil().unwrap();
+        let setup = trips.get();
+        assert_eq!(esil.steps(300).unwrap(), None);
+        assert_eq!(steps.get(), 300);
+        // reading the pc, decoding the instruction and stepping
+        assert_eq!(trips.get() - setup, 3 * 300);
+        assert_eq!(esil.pc().unwrap(), 1);
+
+        // the emulation stops at the illegal instruction
+        let (mut r2, steps, _) = fake_vm(0x10);
+        let trap = r2.esil().unwrap().steps(5).unwrap().unwrap();
+        assert_eq!(trap.addr, 0x10);
+        assert_eq!(trap.message, "ill instruction");
+        assert_eq!(steps.get(), 0);
+    }
+}
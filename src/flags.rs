@@ -0,0 +1,140 @@
+//! Flags, comments and metadata annotations.
+//!
+//! Wraps `f`, `fs`, `CC`, `Cd` and `Cs` together with their JSON listings.
+//! User-supplied names are validated and comment text is passed verbatim,
+//! so neither can be interpreted as additional r2 commands.
+
+use crate::util::{check_name, check_single_line};
+use crate::{R2Pipe, Result};
+
+use serde_derive::Deserialize;
+
+/// A flag, as listed by `fj`.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub struct Flag {
+    pub name: String,
+    #[serde(default)]
+    pub realname: String,
+    #[serde(default)]
+    pub size: u64,
+    pub offset: u64,
+}
+
+/// A flag space, as listed by `fsj`.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub struct FlagSpace {
+    pub name: String,
+    #[serde(default)]
+    pub count: u64,
+    #[serde(default)]
+    pub selected: bool,
+}
+
+/// A comment, as listed by `CCj`.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub struct Comment {
+    #[serde(rename = "offset")]
+    pub addr: u64,
+    #[serde(rename = "name")]
+    pub text: String,
+}
+
+/// A metadata item, as listed by `Cj`.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub struct MetaItem {
+    pub offset: u64,
+    /// Kind of item, e.g. `Cd` for data, `Cs` for strings, `CCu` for
+    /// comments.
+    #[serde(rename = "type", default)]
+    pub kind: String,
+    pub size: Option<u64>,
+    #[serde(default)]
+    pub name: String,
+}
+
+impl R2Pipe {
+    /// Adds a flag named `name` covering `size` bytes at `addr`.
+    pub fn add_flag(&mut self, name: &str, addr: u64, size: u64) -> Result<()> {
+        self.cmd(&format!("f {} {} @ {:#x}", check_name(name)?, size, addr))?;
+        Ok(())
+    }
+
+    pub fn remove_flag(&mut self, name: &str) -> Result<()> {
+        self.cmd(&format!("f-{}", check_name(name)?))?;
+        Ok(())
+    }
+
+    /// Lists the flags of the given flag space, or of all of them. The
+    /// selected flag space is left untouched.
+    pub fn flags(&mut self, space: Option<&str>) -> Result<Vec<Flag>> {
+        let space = match space {
+            Some(space) => check_name(space)?,
+            None => return self.flags_in_selected_space("fs *"),
+        };
+        self.flags_in_selected_space(&format!("fs {}", space))
+    }
+
+    fn flags_in_selected_space(&mut self, select: &str) -> Result<Vec<Flag>> {
+        let previous = self
+            .flagspaces()?
+            .into_iter()
+            .find(|s| s.selected)
+            .map(|s| format!("fs {}", s.name))
+            .unwrap_or_else(|| "fs *".to_string());
+        self.cmd(select)?;
+        let res = self.cmdj_or_empty("fj");
+        self.cmd(&previous)?;
+        res
+    }
+
+    pub fn flagspaces(&mut self) -> Result<Vec<FlagSpace>> {
+        self.cmdj_or_empty("fsj")
+    }
+
+    /// Sets the comment at `addr`, replacing any previous one.
+    pub fn set_comment(&mut self, addr: u64, text: &str) -> Result<()> {
+        let text = check_single_line(text)?;
+        self.cmd(&format!("CC- @ {:#x}", addr))?;
+        // "" disables the interpretation of `;`, `@`, `|`, ... in the
+        // comment, so the address has to be given by seeking instead
+        let mut r2 = self.seek_guard()?;
+        r2.seek_to(addr)?;
+        r2.call(&format!("CC {}", text))?;
+        Ok(())
+    }
+
+    /// Returns the comment at `addr`, if any.
+    pub fn comment_at(&mut self, addr: u64) -> Result<Option<String>> {
+        Ok(self
+            .comments()?
+            .into_iter()
+            .find(|c| c.addr == addr)
+            .map(|c| c.text))
+    }
+
+    pub fn remove_comment(&mut self, addr: u64) -> Result<()> {
+        self.cmd(&format!("CC- @ {:#x}", addr))?;
+        Ok(())
+    }
+
+    pub fn comments(&mut self) -> Result<Vec<Comment>> {
+        self.cmdj_or_empty("CCj")
+    }
+
+    /// Marks `size` bytes at `addr` as data (`Cd`).
+    pub fn set_data(&mut self, addr: u64, size: u64) -> Result<()> {
+        self.cmd(&format!("Cd {} @ {:#x}", size, addr))?;
+        Ok(())
+    }
+
+    /// Marks `size` bytes at `addr` as a string (`Cs`).
+    pub fn set_string(&mut self, addr: u64, size: u64) -> Result<()> {
+        self.cmd(&format!("Cs {} @ {:#x}", size, addr))?;
+        Ok(())
+    }
+
+    /// Lists all the metadata items: comments, data, strings, ... (`Cj`).
+    pub fn metadata(&mut self) -> Result<Vec<MetaItem>> {
+        self.cmdj_or_empty("Cj")
+    }
+}
@@ -0,0 +1,123 @@
+//! Named print formats (`pf`) and structured decoding.
+//!
+//! Formats registered with `pf.name spec` can be decoded at any address
+//! into `Field`s, into a `serde_json::Value` tree keyed by field name, or
+//! directly into any `Deserialize` type.
+
+use crate::types::{flatten_fields, Field};
+use crate::util::{check_name, check_single_line};
+use crate::{Error, R2Pipe, Result};
+
+use serde::de::DeserializeOwned;
+use serde_json::{Map, Value};
+
+/// A named `pf` format.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Format {
+    pub name: String,
+    /// Format specification, e.g. `dd width height`.
+    pub spec: String,
+}
+
+/// Converts decoded fields into an object mapping field names to values,
+/// nested structs becoming nested objects.
+pub fn fields_to_value(fields: &[Field]) -> Value {
+    let mut map = Map::new();
+    for f in fields {
+        let value = if f.fields.is_empty() {
+            f.value.clone()
+        } else {
+            fields_to_value(&f.fields)
+        };
+        map.insert(f.name.clone(), value);
+    }
+    Value::Object(map)
+}
+
+fn check_spec(spec: &str) -> Result<&str> {
+    let spec = check_single_line(spec)?.trim();
+    if spec.is_empty() || spec.contains([';', '|', '@', '>', '`', '~']) {
+        return Err(Error::InvalidArgument(format!("invalid format {:?}", spec)));
+    }
+    Ok(spec)
+}
+
+impl R2Pipe {
+    /// Registers the format `spec` under `name` (`pf.name spec`).
+    pub fn define_format(&mut self, name: &str, spec: &str) -> Result<Format> {
+        let (name, spec) = (check_name(name)?, check_spec(spec)?);
+        self.cmd(&format!("pf.{} {}", name, spec))?;
+        Ok(Format {
+            name: name.to_string(),
+            spec: spec.to_string(),
+        })
+    }
+
+    /// Lists the registered formats (`pf.`).
+    pub fn formats(&mut self) -> Result<Vec<Format>> {
+        let out = self.cmd("pf.")?;
+        Ok(out
+            .lines()
+            .filter_map(|l| {
+                let (name, spec) = l.trim().strip_prefix("pf.")?.split_once(' ')?;
+                Some(Format {
+                    name: name.to_string(),
+                    spec: spec.trim().to_string(),
+                })
+            })
+            .collect())
+    }
+
+    /// Decodes the registered format `name` at `addr` (`pfj.name`).
+    pub fn decode_fields(&mut self, name: &str, addr: u64) -> Result<Vec<Field>> {
+        let cmd = format!("pfj.{} @ {:#x}", check_name(name)?, addr);
+        self.decode_cmd(&cmd)
+    }
+
+    /// Decodes an unregistered format specification at `addr`.
+    pub fn decode_spec(&mut self, spec: &str, addr: u64) -> Result<Vec<Field>> {
+        let cmd = format!("pfj {} @ {:#x}", check_spec(spec)?, addr);
+        self.decode_cmd(&cmd)
+    }
+
+    /// Decodes the registered format `name` at `addr` into a value tree.
+    pub fn decode_value(&mut self, name: &str, addr: u64) -> Result<Value> {
+        Ok(fields_to_value(&self.decode_fields(name, addr)?))
+    }
+
+    /// Decodes the registered format `name` at `addr` into `T`, matching
+    /// the struct fields of `T` with the field names of the format.
+    pub fn decode<T: DeserializeOwned>(&mut self, name: &str, addr: u64) -> Result<T> {
+        Ok(serde_json::from_value(self.decode_value(name, addr)?)?)
+    }
+
+    fn decode_cmd(&mut self, cmd: &str) -> Result<Vec<Field>> {
+        match self.cmdj(cmd)? {
+            Value::Array(items) => Ok(flatten_fields(&items)),
+            other => Err(Error::CommandFailed(format!(
+                "unexpected output from pfj: {}",
+                other
+            ))),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::fields_to_value;
+    use crate::types::flatten_fields;
+    use serde_json::json;
+
+    #[test]
+    fn value_tree() {
+        let pfj = json!([
+            {"name": "id", "type": "d", "offset": 0, "value": 7},
+            {"name": "pos", "type": "?", "offset": 4, "value": [
+                {"name": "x", "type": "w", "offset": 4, "value": 1},
+                {"name": "y", "type": "w", "offset": 6, "value": 2}
+            ]}
+        ]);
+        let value = fields_to_value(&flatten_fields(pfj.as_array().unwrap()));
+        assert_eq!(value, json!({"id": 7, "pos": {"x": 1, "y": 2}}));
+    }
+}
@@ -0,0 +1,194 @@
+//! Function management: listing, renaming, signatures and variables.
+//!
+//! Wraps `afl`, `afi`, `afn`, `afs`, `afv` and `af+`/`af-`. Names are
+//! validated and signatures and types are passed verbatim to r2.
+
+use crate::util::{check_name, check_single_line};
+use crate::{Error, R2Pipe, Result};
+
+use serde_derive::Deserialize;
+use serde_json::Value;
+
+/// A function, as listed by `aflj` and `afij`.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub struct Function {
+    #[serde(rename = "offset")]
+    pub addr: u64,
+    pub name: String,
+    #[serde(default)]
+    pub size: u64,
+    /// Number of basic blocks.
+    #[serde(default)]
+    pub nbbs: u64,
+    #[serde(default)]
+    pub edges: u64,
+    #[serde(default)]
+    pub nargs: u64,
+    #[serde(default)]
+    pub nlocals: u64,
+    #[serde(default)]
+    pub noreturn: bool,
+    /// Calling convention, e.g. `amd64`.
+    #[serde(default)]
+    pub calltype: String,
+    pub signature: Option<String>,
+}
+
+/// Where a variable is stored.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Storage {
+    /// In the given register.
+    Register(String),
+    /// On the stack, relative to the stack pointer.
+    Stack(i64),
+    /// On the stack, relative to the base pointer.
+    Frame(i64),
+}
+
+/// A local variable or argument of a function, as listed by `afvj`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Variable {
+    pub name: String,
+    /// C type of the variable.
+    pub kind: String,
+    pub is_arg: bool,
+    pub storage: Storage,
+}
+
+impl Variable {
+    fn from_afvj(v: &Value, class: &str) -> Option<Variable> {
+        let offset = || v["ref"]["offset"].as_i64().unwrap_or(0);
+        let storage = match class {
+            "reg" => Storage::Register(
+                v["ref"]
+                    .as_str()
+                    .or_else(|| v["ref"]["base"].as_str())
+                    .unwrap_or_default()
+                    .to_string(),
+            ),
+            "sp" => Storage::Stack(offset()),
+            "bp" => Storage::Frame(offset()),
+            _ => return None,
+        };
+        Some(Variable {
+            name: v["name"].as_str()?.to_string(),
+            kind: v["type"].as_str().unwrap_or_default().to_string(),
+            is_arg: v["kind"].as_str() == Some("arg"),
+            storage,
+        })
+    }
+}
+
+impl R2Pipe {
+    /// Lists all the functions found by the analysis (`aflj`).
+    pub fn functions(&mut self) -> Result<Vec<Function>> {
+        self.cmdj_or_empty("aflj")
+    }
+
+    /// Returns the function containing `addr`, if any (`afij`).
+    pub fn function_info(&mut self, addr: u64) -> Result<Option<Function>> {
+        let mut res: Vec<Function> = self.cmdj_or_empty(&format!("afij @ {:#x}", addr))?;
+        Ok(if res.is_empty() {
+            None
+        } else {
+            Some(res.swap_remove(0))
+        })
+    }
+
+    /// Creates a function named `name` starting at `addr` (`af+`).
+    pub fn create_function(&mut self, addr: u64, name: &str) -> Result<Function> {
+        self.cmd(&format!("af+ {:#x} {}", addr, check_name(name)?))?;
+        self.function_info(addr)?
+            .filter(|f| f.addr == addr)
+            .ok_or_else(|| Error::CommandFailed(format!("cannot create function at {:#x}", addr)))
+    }
+
+    /// Deletes the function containing `addr` (`af-`).
+    pub fn delete_function(&mut self, addr: u64) -> Result<()> {
+        self.cmd(&format!("af- {:#x}", addr))?;
+        Ok(())
+    }
+
+    pub fn rename_function(&mut self, addr: u64, name: &str) -> Result<()> {
+        self.cmd(&format!("afn {} @ {:#x}", check_name(name)?, addr))?;
+        Ok(())
+    }
+
+    /// Returns the C signature of the function containing `addr` (`afs`).
+    pub fn signature(&mut self, addr: u64) -> Result<String> {
+        Ok(self.cmd(&format!("afs @ {:#x}", addr))?.trim().to_string())
+    }
+
+    /// Sets the C signature of the function containing `addr`, e.g.
+    /// `int main(int argc, char **argv)`.
+    pub fn set_signature(&mut self, addr: u64, signature: &str) -> Result<()> {
+        let signature = check_single_line(signature)?;
+        let mut r2 = self.seek_guard()?;
+        r2.seek_to(addr)?;
+        r2.call(&format!("afs {}", signature))?;
+        Ok(())
+    }
+
+    /// Lists the variables and arguments of the function containing `addr`.
+    pub fn variables(&mut self, addr: u64) -> Result<Vec<Variable>> {
+        let vars = match self.cmdj(&format!("afvj @ {:#x}", addr)) {
+            Ok(vars) => vars,
+            Err(Error::EmptyResponse) => return Ok(Vec::new()),
+            Err(e) => return Err(e),
+        };
+        let mut res = Vec::new();
+        for class in ["reg", "sp", "bp"] {
+            for v in vars[class].as_array().into_iter().flatten() {
+                res.extend(Variable::from_afvj(v, class));
+            }
+        }
+        Ok(res)
+    }
+
+    /// Lists the arguments of the function containing `addr`.
+    pub fn arguments(&mut self, addr: u64) -> Result<Vec<Variable>> {
+        let mut vars = self.variables(addr)?;
+        vars.retain(|v| v.is_arg);
+        Ok(vars)
+    }
+
+    /// Renames a variable of the function containing `addr` (`afvn`).
+    pub fn rename_variable(&mut self, addr: u64, old: &str, new: &str) -> Result<()> {
+        self.cmd(&format!(
+            "afvn {} {} @ {:#x}",
+            check_name(new)?,
+            check_name(old)?,
+            addr
+        ))?;
+        Ok(())
+    }
+
+    /// Changes the type of a variable of the function containing `addr`
+    /// (`afvt`), e.g. to `char *`.
+    pub fn retype_variable(&mut self, addr: u64, name: &str, kind: &str) -> Result<()> {
+        let (name, kind) = (check_name(name)?, check_single_line(kind)?);
+        let mut r2 = self.seek_guard()?;
+        r2.seek_to(addr)?;
+        r2.call(&format!("afvt {} {}", name, kind))?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{Storage, Variable};
+    use serde_json::json;
+
+    #[test]
+    fn variables_from_afvj() {
+        let arg = json!({"name": "argc", "kind": "arg", "type": "int", "ref": "rdi"});
+        let var = json!({"name": "var_8h", "kind": "var", "type": "int64_t",
+                         "ref": {"base": "rbp", "offset": -8}});
+        let arg = Variable::from_afvj(&arg, "reg").unwrap();
+        assert!(arg.is_arg);
+        assert_eq!(arg.storage, Storage::Register("rdi".to_string()));
+        let var = Variable::from_afvj(&var, "bp").unwrap();
+        assert!(!var.is_arg);
+        assert_eq!(var.storage, Storage::Frame(-8));
+    }
+}
@@ -0,0 +1,477 @@
+//! Control-flow graphs of functions, built from `agfj` and `afbj`.
+//!
+//! Besides giving access to the basic blocks and their edges, the graph
+//! provides dominator and natural loop analysis as well as DOT and GraphML
+//! export.
+
+use crate::disasm::Instruction;
+use crate::{Error, R2Pipe, Result};
+
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::fmt::Write;
+
+use serde_derive::Deserialize;
+use serde_json::Value;
+
+/// Kind of control-flow transfer between two basic blocks.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum EdgeKind {
+    /// Unconditional jump, or the taken branch of a conditional jump.
+    Jump,
+    /// Fall-through, or the not-taken branch of a conditional jump.
+    Fail,
+    /// A case of a switch table, holding the case value.
+    Switch(u64),
+}
+
+/// A directed edge between two basic blocks, identified by their address.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Edge {
+    pub from: u64,
+    pub to: u64,
+    pub kind: EdgeKind,
+}
+
+/// A basic block and its instructions.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BasicBlock {
+    pub addr: u64,
+    pub size: u64,
+    pub instructions: Vec<Instruction>,
+}
+
+/// A natural loop, identified by its header block.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Loop {
+    pub header: u64,
+    /// Blocks with a back edge to the header.
+    pub latches: Vec<u64>,
+    /// All the blocks of the loop, header included.
+    pub body: BTreeSet<u64>,
+}
+
+/// Immediate dominators of the blocks reachable from the entry block.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DominatorTree {
+    entry: u64,
+    idom: BTreeMap<u64, u64>,
+}
+
+impl DominatorTree {
+    /// Immediate dominator of `block`, `None` for the entry block and for
+    /// blocks unreachable from it.
+    pub fn idom(&self, block: u64) -> Option<u64> {
+        if block == self.entry {
+            return None;
+        }
+        self.idom.get(&block).copied()
+    }
+
+    /// Whether `a` dominates `b`. Every block dominates itself.
+    pub fn dominates(&self, a: u64, b: u64) -> bool {
+        if !self.idom.contains_key(&b) {
+            return false;
+        }
+        let mut cur = b;
+        loop {
+            if cur == a {
+                return true;
+            }
+            match self.idom(cur) {
+                Some(next) => cur = next,
+                None => return false,
+            }
+        }
+    }
+
+    /// Blocks immediately dominated by `block`.
+    pub fn children(&self, block: u64) -> Vec<u64> {
+        self.idom
+            .iter()
+            .filter(|&(&b, &d)| d == block && b != self.entry)
+            .map(|(&b, _)| b)
+            .collect()
+    }
+}
+
+/// Control-flow graph of a single function.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ControlFlowGraph {
+    pub name: String,
+    pub entry: u64,
+    /// Basic blocks ordered by address.
+    pub blocks: Vec<BasicBlock>,
+    /// Edges between blocks of the function. Transfers leaving the function
+    /// are not included.
+    pub edges: Vec<Edge>,
+}
+
+#[derive(Deserialize)]
+struct AgfFunction {
+    #[serde(default)]
+    name: String,
+    offset: u64,
+    #[serde(default)]
+    blocks: Vec<AgfBlock>,
+}
+
+#[derive(Deserialize)]
+struct AgfBlock {
+    offset: u64,
+    #[serde(default)]
+    size: u64,
+    jump: Option<u64>,
+    fail: Option<u64>,
+    #[serde(default)]
+    ops: Vec<Instruction>,
+}
+
+#[derive(Deserialize)]
+struct AfbBlock {
+    addr: u64,
+    switch_op: Option<SwitchOp>,
+}
+
+#[derive(Deserialize)]
+struct SwitchOp {
+    #[serde(default)]
+    cases: Vec<SwitchCase>,
+}
+
+#[derive(Deserialize)]
+struct SwitchCase {
+    jump: u64,
+    value: u64,
+}
+
+impl ControlFlowGraph {
+    fn from_parts(fcn: AgfFunction, afb: Vec<AfbBlock>) -> ControlFlowGraph {
+        let addrs: BTreeSet<u64> = fcn.blocks.iter().map(|b| b.offset).collect();
+        let mut edges = Vec::new();
+        let mut blocks = Vec::new();
+        for b in fcn.blocks {
+            if let Some(to) = b.jump.filter(|a| addrs.contains(a)) {
+                edges.push(Edge {
+                    from: b.offset,
+                    to,
+                    kind: EdgeKind::Jump,
+                });
+            }
+            if let Some(to) = b.fail.filter(|a| addrs.contains(a)) {
+                edges.push(Edge {
+                    from: b.offset,
+                    to,
+                    kind: EdgeKind::Fail,
+                });
+            }
+            blocks.push(BasicBlock {
+                addr: b.offset,
+                size: b.size,
+                instructions: b.ops,
+            });
+        }
+        for b in afb {
+            let cases = b.switch_op.map(|s| s.cases).unwrap_or_default();
+            for case in cases.into_iter().filter(|c| addrs.contains(&c.jump)) {
+                edges.push(Edge {
+                    from: b.addr,
+                    to: case.jump,
+                    kind: EdgeKind::Switch(case.value),
+                });
+            }
+        }
+        blocks.sort_by_key(|b| b.addr);
+        ControlFlowGraph {
+            name: fcn.name,
+            entry: fcn.offset,
+            blocks,
+            edges,
+        }
+    }
+
+    /// Returns the block starting at `addr`.
+    pub fn block(&self, addr: u64) -> Option<&BasicBlock> {
+        self.blocks
+            .binary_search_by_key(&addr, |b| b.addr)
+            .ok()
+            .map(|i| &self.blocks[i])
+    }
+
+    /// Returns the block containing the instruction at `addr`.
+    pub fn block_containing(&self, addr: u64) -> Option<&BasicBlock> {
+        self.blocks
+            .iter()
+            .find(|b| b.addr <= addr && addr < b.addr + b.size)
+    }
+
+    pub fn successors(&self, block: u64) -> Vec<u64> {
+        self.edges
+            .iter()
+            .filter(|e| e.from == block)
+            .map(|e| e.to)
+            .collect()
+    }
+
+    pub fn predecessors(&self, block: u64) -> Vec<u64> {
+        self.edges
+            .iter()
+            .filter(|e| e.to == block)
+            .map(|e| e.from)
+            .collect()
+    }
+
+    /// Blocks reachable from the entry, in reverse postorder.
+    fn reverse_postorder(&self) -> Vec<u64> {
+        let succs = self.successor_map();
+        let mut visited = BTreeSet::new();
+        let mut order = Vec::new();
+        // iterative DFS keeping the index of the next successor to visit
+        let mut stack = vec![(self.entry, 0)];
+        visited.insert(self.entry);
+        while let Some((block, next)) = stack.pop() {
+            let out = succs.get(&block).map(Vec::as_slice).unwrap_or(&[]);
+            if let Some(&succ) = out.get(next) {
+                stack.push((block, next + 1));
+                if visited.insert(succ) {
+                    stack.push((succ, 0));
+                }
+            } else {
+                order.push(block);
+            }
+        }
+        order.reverse();
+        order
+    }
+
+    fn successor_map(&self) -> HashMap<u64, Vec<u64>> {
+        let mut map: HashMap<u64, Vec<u64>> = HashMap::new();
+        for e in &self.edges {
+            map.entry(e.from).or_default().push(e.to);
+        }
+        map
+    }
+
+    /// Computes the dominator tree using the Cooper-Harvey-Kennedy
+    /// iterative algorithm.
+    pub fn dominators(&self) -> DominatorTree {
+        let order = self.reverse_postorder();
+        let index: HashMap<u64, usize> = order.iter().enumerate().map(|(i, &b)| (b, i)).collect();
+        let mut preds: Vec<Vec<usize>> = vec![Vec::new(); order.len()];
+        for e in &self.edges {
+            if let (Some(&f), Some(&t)) = (index.get(&e.from), index.get(&e.to)) {
+                preds[t].push(f);
+            }
+        }
+
+        let mut idom: Vec<Option<usize>> = vec![None; order.len()];
+        if !order.is_empty() {
+            idom[0] = Some(0);
+        }
+        let mut changed = true;
+        while changed {
+            changed = false;
+            for b in 1..order.len() {
+                let mut new_idom = None;
+                for &p in &preds[b] {
+                    if idom[p].is_none() {
+                        continue;
+                    }
+                    new_idom = Some(match new_idom {
+                        None => p,
+                        Some(cur) => intersect(&idom, p, cur),
+                    });
+                }
+                if new_idom.is_some() && idom[b] != new_idom {
+                    idom[b] = new_idom;
+                    changed = true;
+                }
+            }
+        }
+
+        DominatorTree {
+            entry: self.entry,
+            idom: idom
+                .iter()
+                .enumerate()
+                .filter_map(|(b, d)| d.map(|d| (order[b], order[d])))
+                .collect(),
+        }
+    }
+
+    /// Finds the natural loops of the function. Back edges sharing the same
+    /// header are merged into a single loop.
+    pub fn loops(&self) -> Vec<Loop> {
+        let dom = self.dominators();
+        let mut loops: BTreeMap<u64, Loop> = BTreeMap::new();
+        for e in self.edges.iter().filter(|e| dom.dominates(e.to, e.from)) {
+            let lp = loops.entry(e.to).or_insert_with(|| Loop {
+                header: e.to,
+                latches: Vec::new(),
+                body: std::iter::once(e.to).collect(),
+            });
+            if !lp.latches.contains(&e.from) {
+                lp.latches.push(e.from);
+            }
+            let mut work = vec![e.from];
+            while let Some(b) = work.pop() {
+                if lp.body.insert(b) {
+                    work.extend(self.predecessors(b));
+                }
+            }
+        }
+        loops.into_values().collect()
+    }
+
+    /// Renders the graph in Graphviz DOT format.
+    pub fn to_dot(&self) -> String {
+        let mut out = String::new();
+        let _ = writeln!(out, "digraph \"{}\" {{", escape(&self.name, '"'));
+        let _ = writeln!(out, "  node [shape=box fontname=\"monospace\"];");
+        for b in &self.blocks {
+            let mut label = format!("{:#x}:\\l", b.addr);
+            for ins in &b.instructions {
+                label += &format!("{}\\l", escape(&ins.opcode, '"'));
+            }
+            let _ = writeln!(out, "  \"{:#x}\" [label=\"{}\"];", b.addr, label);
+        }
+        for e in &self.edges {
+            let attrs = match e.kind {
+                EdgeKind::Jump => "color=green".to_string(),
+                EdgeKind::Fail => "color=red".to_string(),
+                EdgeKind::Switch(v) => format!("color=blue label=\"case {}\"", v),
+            };
+            let _ = writeln!(out, "  \"{:#x}\" -> \"{:#x}\" [{}];", e.from, e.to, attrs);
+        }
+        out.push_str("}\n");
+        out
+    }
+
+    /// Renders the graph in GraphML format.
+    pub fn to_graphml(&self) -> String {
+        let mut out = String::new();
+        out.push_str("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+        out.push_str("<graphml xmlns=\"http://graphml.graphdrawing.org/xmlns\">\n");
+        out.push_str("  <key id=\"size\" for=\"node\" attr.name=\"size\" attr.type=\"long\"/>\n");
+        out.push_str("  <key id=\"code\" for=\"node\" attr.name=\"code\" attr.type=\"string\"/>\n");
+        out.push_str("  <key id=\"kind\" for=\"edge\" attr.name=\"kind\" attr.type=\"string\"/>\n");
+        let _ = writeln!(
+            out,
+            "  <graph id=\"{}\" edgedefault=\"directed\">",
+            xml_escape(&self.name)
+        );
+        for b in &self.blocks {
+            let code: Vec<&str> = b.instructions.iter().map(|i| i.opcode.as_str()).collect();
+            let _ = writeln!(out, "    <node id=\"{:#x}\">", b.addr);
+            let _ = writeln!(out, "      <data key=\"size\">{}</data>", b.size);
+            let _ = writeln!(
+                out,
+                "      <data key=\"code\">{}</data>",
+                xml_escape(&code.join("\n"))
+            );
+            out.push_str("    </node>\n");
+        }
+        for e in &self.edges {
+            let kind = match e.kind {
+                EdgeKind::Jump => "jump".to_string(),
+                EdgeKind::Fail => "fail".to_string(),
+                EdgeKind::Switch(v) => format!("case {}", v),
+            };
+            let _ = writeln!(
+                out,
+                "    <edge source=\"{:#x}\" target=\"{:#x}\"><data key=\"kind\">{}</data></edge>",
+                e.from, e.to, kind
+            );
+        }
+        out.push_str("  </graph>\n</graphml>\n");
+        out
+    }
+}
+
+fn intersect(idom: &[Option<usize>], mut a: usize, mut b: usize) -> usize {
+    while a != b {
+        while a > b {
+            a = idom[a].unwrap_or(0);
+        }
+        while b > a {
+            b = idom[b].unwrap_or(0);
+        }
+    }
+    a
+}
+
+fn escape(s: &str, quote: char) -> String {
+    s.replace('\\', "\\\\")
+        .replace(quote, &format!("\\{}", quote))
+}
+
+fn xml_escape(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+impl R2Pipe {
+    /// Builds the control-flow graph of the function containing `addr`.
+    pub fn control_flow_graph(&mut self, addr: u64) -> Result<ControlFlowGraph> {
+        let agf = match self.cmdj(&format!("agfj @ {:#x}", addr)) {
+            Ok(Value::Array(mut fcns)) if !fcns.is_empty() => fcns.swap_remove(0),
+            Ok(v @ Value::Object(_)) => v,
+            Ok(_) | Err(Error::EmptyResponse) => {
+                return Err(Error::CommandFailed(format!("no function at {:#x}", addr)))
+            }
+            Err(e) => return Err(e),
+        };
+        let afb = self.cmdj(&format!("afbj @ {:#x}", addr))?;
+        Ok(ControlFlowGraph::from_parts(
+            serde_json::from_value(agf)?,
+            serde_json::from_value(afb)?,
+        ))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{ControlFlowGraph, EdgeKind};
+
+    // entry -> a -> b -> a (loop), b -> exit, entry -> switch to a/exit
+    fn sample() -> ControlFlowGraph {
+        let agf = r#"{"name":"f","offset":0,"blocks":[
+            {"offset":0,"size":4,"jump":16,"fail":4},
+            {"offset":4,"size":4,"jump":8},
+            {"offset":8,"size":4,"jump":4,"fail":12},
+            {"offset":12,"size":4},
+            {"offset":16,"size":4}]}"#;
+        let afb = r#"[{"addr":16,"switch_op":{"cases":[
+            {"addr":16,"jump":4,"value":0},{"addr":16,"jump":12,"value":1}]}}]"#;
+        ControlFlowGraph::from_parts(
+            serde_json::from_str(agf).unwrap(),
+            serde_json::from_str(afb).unwrap(),
+        )
+    }
+
+    #[test]
+    fn dominators_and_loops() {
+        let cfg = sample();
+        assert!(cfg.edges.iter().any(|e| e.kind == EdgeKind::Switch(1)));
+        let dom = cfg.dominators();
+        assert_eq!(dom.idom(0), None);
+        assert_eq!(dom.idom(4), Some(0));
+        assert_eq!(dom.idom(8), Some(4));
+        assert_eq!(dom.idom(12), Some(0));
+        assert!(dom.dominates(4, 8));
+        assert!(!dom.dominates(8, 12));
+
+        let loops = cfg.loops();
+        assert_eq!(loops.len(), 1);
+        assert_eq!(loops[0].header, 4);
+        assert_eq!(loops[0].latches, vec![8]);
+        assert_eq!(
+            loops[0].body.iter().copied().collect::<Vec<_>>(),
+            vec![4, 8]
+        );
+        assert!(cfg
+            .to_dot()
+            .contains("\"0x10\" -> \"0xc\" [color=blue label=\"case 1\"]"));
+    }
+}
@@ -0,0 +1,184 @@
+//! Hashes and entropy of memory regions and of the opened file.
+//!
+//! The algorithms available depend on how r2 was built; `hash_algorithms`
+//! returns the ones the connected instance supports (`phl`).
+
+use crate::util::{check_name, decode_hex, parse_u64};
+use crate::{Error, R2Pipe, Result};
+
+use serde_derive::Deserialize;
+use std::fmt;
+
+/// A hashing or checksum algorithm known to r2.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum HashAlgorithm {
+    Md4,
+    Md5,
+    Sha1,
+    Sha256,
+    Sha384,
+    Sha512,
+    Crc32,
+    Adler32,
+    Xxhash,
+    /// Shannon entropy, reported as a number of bits per byte.
+    Entropy,
+    /// Any other algorithm reported by r2.
+    Other(String),
+}
+
+impl From<&str> for HashAlgorithm {
+    fn from(name: &str) -> HashAlgorithm {
+        match name {
+            "md4" => HashAlgorithm::Md4,
+            "md5" => HashAlgorithm::Md5,
+            "sha1" => HashAlgorithm::Sha1,
+            "sha256" => HashAlgorithm::Sha256,
+            "sha384" => HashAlgorithm::Sha384,
+            "sha512" => HashAlgorithm::Sha512,
+            "crc32" => HashAlgorithm::Crc32,
+            "adler32" => HashAlgorithm::Adler32,
+            "xxhash" => HashAlgorithm::Xxhash,
+            "entropy" => HashAlgorithm::Entropy,
+            other => HashAlgorithm::Other(other.to_string()),
+        }
+    }
+}
+
+impl fmt::Display for HashAlgorithm {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            HashAlgorithm::Md4 => "md4",
+            HashAlgorithm::Md5 => "md5",
+            HashAlgorithm::Sha1 => "sha1",
+            HashAlgorithm::Sha256 => "sha256",
+            HashAlgorithm::Sha384 => "sha384",
+            HashAlgorithm::Sha512 => "sha512",
+            HashAlgorithm::Crc32 => "crc32",
+            HashAlgorithm::Adler32 => "adler32",
+            HashAlgorithm::Xxhash => "xxhash",
+            HashAlgorithm::Entropy => "entropy",
+            HashAlgorithm::Other(name) => name,
+        };
+        f.write_str(name)
+    }
+}
+
+/// Result of hashing a region.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Digest {
+    pub algorithm: HashAlgorithm,
+    /// Value as printed by r2, hexadecimal for hashes and checksums.
+    pub value: String,
+}
+
+impl Digest {
+    /// Raw bytes of the digest, `None` if the value is not hexadecimal.
+    pub fn bytes(&self) -> Option<Vec<u8>> {
+        decode_hex(self.value.trim_start_matches("0x"))
+    }
+}
+
+/// Entropy of one block of an `EntropyMap`.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct BlockEntropy {
+    #[serde(alias = "offset")]
+    pub addr: u64,
+    /// Entropy scaled by r2 to the `0..=255` range.
+    #[serde(alias = "entropy")]
+    pub value: f64,
+}
+
+/// Entropy of consecutive blocks of the file (`p=ej`).
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct EntropyMap {
+    #[serde(rename = "blocksize", default)]
+    pub block_size: u64,
+    #[serde(rename = "address", default)]
+    pub addr: u64,
+    #[serde(default)]
+    pub size: u64,
+    #[serde(rename = "entropy", default)]
+    pub blocks: Vec<BlockEntropy>,
+}
+
+fn check_algorithm(algo: &HashAlgorithm) -> Result<String> {
+    Ok(check_name(&algo.to_string())?.to_string())
+}
+
+impl R2Pipe {
+    /// Lists the algorithms supported by the connected r2 (`phl`).
+    pub fn hash_algorithms(&mut self) -> Result<Vec<HashAlgorithm>> {
+        let out = self.cmd("phl")?;
+        Ok(out
+            .lines()
+            .filter_map(|l| l.split_whitespace().last())
+            .map(HashAlgorithm::from)
+            .collect())
+    }
+
+    /// Hashes the `len` bytes at `addr` (`ph`).
+    pub fn hash(&mut self, algo: &HashAlgorithm, addr: u64, len: u64) -> Result<Digest> {
+        let out = self.cmd(&format!(
+            "ph {} {} @ {:#x}",
+            check_algorithm(algo)?,
+            len,
+            addr
+        ))?;
+        let value = out.trim();
+        if value.is_empty() {
+            return Err(Error::CommandFailed(format!("cannot compute {}", algo)));
+        }
+        Ok(Digest {
+            algorithm: algo.clone(),
+            value: value.to_string(),
+        })
+    }
+
+    /// Hashes the whole opened file with each of `algos`, like `rahash2`
+    /// would, regardless of how it is mapped in memory.
+    pub fn hash_file(&mut self, algos: &[HashAlgorithm]) -> Result<Vec<Digest>> {
+        self.with_config(&[("io.va", "false")], |r2| {
+            let out = r2.cmd("?v $s")?;
+            let size = parse_u64(&out)
+                .ok_or_else(|| Error::CommandFailed(format!("invalid file size {:?}", out)))?;
+            algos.iter().map(|algo| r2.hash(algo, 0, size)).collect()
+        })
+    }
+
+    /// Computes the entropy of the file split in `blocks` blocks (`p=ej`).
+    pub fn entropy_map(&mut self, blocks: u64) -> Result<EntropyMap> {
+        Ok(serde_json::from_value(
+            self.cmdj(&format!("p=ej {}", blocks))?,
+        )?)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{EntropyMap, HashAlgorithm};
+    use serde_json::json;
+
+    #[test]
+    fn algorithm_names() {
+        for name in ["md5", "sha256", "entropy", "blake3"] {
+            assert_eq!(HashAlgorithm::from(name).to_string(), name);
+        }
+        assert_eq!(
+            HashAlgorithm::from("blake3"),
+            HashAlgorithm::Other("blake3".into())
+        );
+    }
+
+    #[test]
+    fn entropy_from_json() {
+        let map: EntropyMap = serde_json::from_value(json!({
+            "blocksize": 256, "address": 0, "size": 512,
+            "entropy": [{"addr": 0, "value": 12}, {"addr": 256, "value": 231}]
+        }))
+        .unwrap();
+        assert_eq!(map.block_size, 256);
+        assert_eq!(map.blocks[1].addr, 256);
+        assert_eq!(map.blocks[1].value, 231.0);
+    }
+}
@@ -0,0 +1,203 @@
+//! Open files, IO maps and loaded binaries.
+//!
+//! Wraps `o`, `om` and `ob` so several blobs can be loaded at different
+//! addresses, e.g. when analyzing firmware made of multiple images.
+
+use crate::bin::{deserialize_perm, Permissions};
+use crate::util::check_single_line;
+use crate::{Error, R2Pipe, Result};
+
+use serde_derive::Deserialize;
+
+/// An open file descriptor, as listed by `oj`.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub struct OpenFile {
+    pub fd: u64,
+    pub uri: String,
+    #[serde(default)]
+    pub writable: bool,
+    #[serde(default)]
+    pub size: u64,
+}
+
+/// A map of a file descriptor into the address space, as listed by `omj`.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub struct IoMap {
+    #[serde(rename = "map")]
+    pub id: u64,
+    pub fd: u64,
+    /// Offset in the file where the map starts.
+    #[serde(default)]
+    pub delta: u64,
+    pub from: u64,
+    pub to: u64,
+    #[serde(default, deserialize_with = "deserialize_perm")]
+    pub perm: Permissions,
+    #[serde(default)]
+    pub name: String,
+}
+
+/// A loaded binary, as listed by `obj`.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub struct OpenBinary {
+    #[serde(alias = "bfid")]
+    pub id: u64,
+    #[serde(alias = "iofd", default)]
+    pub fd: u64,
+    #[serde(alias = "name", default)]
+    pub file: String,
+    #[serde(alias = "baddr", default)]
+    pub addr: u64,
+    #[serde(default)]
+    pub size: u64,
+    #[serde(default)]
+    pub arch: String,
+    #[serde(default)]
+    pub bits: u64,
+}
+
+impl R2Pipe {
+    /// Opens `uri` (a path, `malloc://size`, `file://path`, ...) mapped at
+    /// `addr` with the given permissions, and returns the new descriptor.
+    pub fn open_file(&mut self, uri: &str, addr: u64, perm: Permissions) -> Result<OpenFile> {
+        let uri = check_single_line(uri)?;
+        if uri.is_empty() || uri.contains(char::is_whitespace) {
+            return Err(Error::InvalidArgument(format!("invalid uri {:?}", uri)));
+        }
+        let before: Vec<u64> = self.files()?.iter().map(|f| f.fd).collect();
+        self.call(&format!("o {} {:#x} {}", uri, addr, perm))?;
+        self.files()?
+            .into_iter()
+            .find(|f| !before.contains(&f.fd))
+            .ok_or_else(|| Error::CommandFailed(format!("cannot open {}", uri)))
+    }
+
+    pub fn close_file(&mut self, fd: u64) -> Result<()> {
+        self.cmd(&format!("o-{}", fd))?;
+        Ok(())
+    }
+
+    pub fn files(&mut self) -> Result<Vec<OpenFile>> {
+        self.cmdj_or_empty("oj")
+    }
+
+    /// Maps `size` bytes of `fd`, starting at file offset `paddr`, at
+    /// `vaddr` and returns the new map.
+    pub fn add_map(
+        &mut self,
+        fd: u64,
+        vaddr: u64,
+        size: u64,
+        paddr: u64,
+        perm: Permissions,
+    ) -> Result<IoMap> {
+        let before: Vec<u64> = self.maps()?.iter().map(|m| m.id).collect();
+        self.cmd(&format!(
+            "om {} {:#x} {:#x} {:#x} {}",
+            fd, vaddr, size, paddr, perm
+        ))?;
+        self.maps()?
+            .into_iter()
+            .find(|m| !before.contains(&m.id))
+            .ok_or_else(|| Error::CommandFailed(format!("cannot map fd {} at {:#x}", fd, vaddr)))
+    }
+
+    pub fn remove_map(&mut self, id: u64) -> Result<()> {
+        self.cmd(&format!("om-{}", id))?;
+        Ok(())
+    }
+
+    pub fn maps(&mut self) -> Result<Vec<IoMap>> {
+        self.cmdj_or_empty("omj")
+    }
+
+    pub fn binaries(&mut self) -> Result<Vec<OpenBinary>> {
+        self.cmdj_or_empty("obj")
+    }
+
+    /// Makes the binary with the given id the current one (`ob`).
+    pub fn select_binary(&mut self, id: u64) -> Result<()> {
+        self.cmd(&format!("ob {}", id))?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{IoMap, OpenBinary, OpenFile};
+    use crate::bin::Permissions;
+    use crate::R2Pipe;
+    use serde_json::json;
+
+    #[test]
+    fn listings_from_json() {
+        let oj = json!([{"raised": false, "fd": 3, "uri": "malloc://512", "from": 0,
+                         "writable": true, "size": 512, "overlaps": false}]);
+        let files: Vec<OpenFile> = serde_json::from_value(oj).unwrap();
+        assert_eq!((files[0].fd, files[0].size), (3, 512));
+        assert!(files[0].writable);
+
+        let omj = json!([{"map": 1, "fd": 3, "delta": 0, "from": 4096, "to": 4607,
+                          "perm": "-rwx", "name": ""}]);
+        let maps: Vec<IoMap> = serde_json::from_value(omj).unwrap();
+        assert_eq!((maps[0].id, maps[0].from, maps[0].to), (1, 4096, 4607));
+        assert!(maps[0].perm.write);
+
+        let obj = json!([{"name": "/bin/ls", "iofd": 3, "bfid": 0, "addr": 4194304,
+                          "size": 142792, "arch": "x86", "bits": 64}]);
+        let bins: Vec<OpenBinary> = serde_json::from_value(obj).unwrap();
+        assert_eq!((bins[0].id, bins[0].fd), (0, 3));
+        assert_eq!(bins[0].file, "/bin/ls");
+    }
+
+    #[test]
+    fn new_entries_found_by_diffing() {
+        let (mut files, mut maps) = (vec![json!({"fd": 3, "uri": "/bin/ls"})], Vec::new());
+        let mut r2 = R2Pipe::from_fn(move |cmd| {
+            match cmd {
+                "oj" => return json!(files).to_string(),
+                "omj" => return json!(maps).to_string(),
+                "\"\"o malloc://16 0x1000 rw-" => {
+                    files.push(json!({"fd": 4, "uri": "malloc://16", "size": 16}))
+                }
+                "om 4 0x2000 0x10 0x0 r--" => maps.push(json!({
+                    "map": 2, "fd": 4, "from": 0x2000, "to": 0x200f, "perm": "r--"
+                })),
+                _ => panic!("unexpected command {}", cmd),
+            }
+            String::new()
+        });
+        let perm = |write| Permissions {
+            read: true,
+            write,
+            exec: false,
+        };
+        let file = r2.open_file("malloc://16", 0x1000, perm(true)).unwrap();
+        assert_eq!(file.fd, 4);
+        let map = r2.add_map(4, 0x2000, 0x10, 0, perm(false)).unwrap();
+        assert_eq!((map.id, map.from), (2, 0x2000));
+        // nothing new shows up when r2 refuses the command
+        assert!(r2.add_map(4, 0x2000, 0x10, 0, perm(false)).is_err());
+    }
+
+    #[test]
+    #[cfg(not(windows))]
+    fn io_test() {
+        let mut pipe = R2Pipe::spawn("malloc://16", None).unwrap();
+        let perm = Permissions {
+            read: true,
+            write: true,
+            exec: false,
+        };
+        let file = pipe.open_file("malloc://64", 0x10000, perm).unwrap();
+        assert_eq!(file.size, 64);
+        let map = pipe.add_map(file.fd, 0x20000, 0x20, 0, perm).unwrap();
+        assert_eq!((map.fd, map.from), (file.fd, 0x20000));
+        pipe.write_bytes(0x20000, b"r2").unwrap();
+        assert_eq!(pipe.read_bytes(0x10000, 2).unwrap(), b"r2");
+        pipe.remove_map(map.id).unwrap();
+        pipe.close_file(file.fd).unwrap();
+        assert!(pipe.files().unwrap().iter().all(|f| f.fd != file.fd));
+        pipe.close();
+    }
+}
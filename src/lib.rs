@@ -55,8 +55,34 @@
 
 #[macro_use]
 pub mod r2pipe;
+pub mod asm;
+pub mod bin;
+pub mod callgraph;
+pub mod classes;
+pub mod config;
+pub mod debug;
+pub mod decompile;
+pub mod diff;
+pub mod disasm;
 mod dlfcn;
+pub mod esil;
+pub mod flags;
+pub mod format;
+pub mod functions;
+pub mod graph;
+pub mod hash;
+pub mod io;
+pub mod memory;
+pub mod project;
 pub mod r2;
+pub mod search;
+pub mod seek;
+pub mod stream;
+pub mod strings;
+pub mod types;
+mod util;
+pub mod xrefs;
+pub mod zignatures;
 
 mod error;
 pub use error::*;
@@ -0,0 +1,211 @@
+//! Memory access on top of `p8` and `wx`.
+//!
+//! Integers are read and written honoring `cfg.bigendian`. Large transfers
+//! are split in chunks so a single response never grows too big.
+
+use crate::util::{decode_hex, encode_hex};
+use crate::{Error, R2Pipe, Result};
+
+/// Maximum number of bytes transferred with a single command.
+const CHUNK_SIZE: u64 = 0x10000;
+/// Number of bytes read at a time while looking for a string terminator.
+const CSTRING_CHUNK: u64 = 256;
+/// Strings longer than this are considered unterminated.
+const CSTRING_MAX: usize = 0x100000;
+
+impl R2Pipe {
+    /// Reads `len` bytes at `addr`.
+    pub fn read_bytes(&mut self, addr: u64, len: u64) -> Result<Vec<u8>> {
+        let mut res = Vec::with_capacity(len.min(CHUNK_SIZE) as usize);
+        let mut done = 0;
+        while done < len {
+            let n = (len - done).min(CHUNK_SIZE);
+            let cur = addr.wrapping_add(done);
+            let out = self.cmd(&format!("p8 {} @ {:#x}", n, cur))?;
+            let bytes = decode_hex(&out)
+                .filter(|b| b.len() as u64 == n)
+                .ok_or_else(|| {
+                    Error::CommandFailed(format!("cannot read {} bytes at {:#x}", n, cur))
+                })?;
+            res.extend(bytes);
+            done += n;
+        }
+        Ok(res)
+    }
+
+    /// Writes `data` at `addr`.
+    pub fn write_bytes(&mut self, addr: u64, data: &[u8]) -> Result<()> {
+        for (i, chunk) in data.chunks(CHUNK_SIZE as usize).enumerate() {
+            let cur = addr.wrapping_add(i as u64 * CHUNK_SIZE);
+            self.cmd(&format!("wx {} @ {:#x}", encode_hex(chunk), cur))?;
+        }
+        Ok(())
+    }
+
+    /// Whether the current configuration uses big endian (`cfg.bigendian`).
+    pub fn is_big_endian(&mut self) -> Result<bool> {
+        Ok(self.cmd("e cfg.bigendian")?.trim() == "true")
+    }
+
+    /// Reads a NUL-terminated string at `addr`, without the terminator.
+    /// Bytes that are not valid UTF-8 are replaced with U+FFFD.
+    pub fn read_cstring(&mut self, addr: u64) -> Result<String> {
+        let mut res = Vec::new();
+        loop {
+            let cur = addr.wrapping_add(res.len() as u64);
+            let chunk = self.read_bytes(cur, CSTRING_CHUNK)?;
+            if let Some(end) = chunk.iter().position(|&b| b == 0) {
+                res.extend_from_slice(&chunk[..end]);
+                break;
+            }
+            res.extend(chunk);
+            if res.len() > CSTRING_MAX {
+                return Err(Error::CommandFailed(format!(
+                    "unterminated string at {:#x}",
+                    addr
+                )));
+            }
+        }
+        Ok(String::from_utf8_lossy(&res).into_owned())
+    }
+
+    fn read_array<const N: usize>(&mut self, addr: u64) -> Result<([u8; N], bool)> {
+        let mut buf = [0; N];
+        buf.copy_from_slice(&self.read_bytes(addr, N as u64)?);
+        Ok((buf, self.is_big_endian()?))
+    }
+
+    pub fn read_u8(&mut self, addr: u64) -> Result<u8> {
+        Ok(self.read_bytes(addr, 1)?[0])
+    }
+
+    pub fn read_u16(&mut self, addr: u64) -> Result<u16> {
+        let (buf, be) = self.read_array(addr)?;
+        Ok(if be {
+            u16::from_be_bytes(buf)
+        } else {
+            u16::from_le_bytes(buf)
+        })
+    }
+
+    pub fn read_u32(&mut self, addr: u64) -> Result<u32> {
+        let (buf, be) = self.read_array(addr)?;
+        Ok(if be {
+            u32::from_be_bytes(buf)
+        } else {
+            u32::from_le_bytes(buf)
+        })
+    }
+
+    pub fn read_u64(&mut self, addr: u64) -> Result<u64> {
+        let (buf, be) = self.read_array(addr)?;
+        Ok(if be {
+            u64::from_be_bytes(buf)
+        } else {
+            u64::from_le_bytes(buf)
+        })
+    }
+
+    pub fn write_u8(&mut self, addr: u64, value: u8) -> Result<()> {
+        self.write_bytes(addr, &[value])
+    }
+
+    pub fn write_u16(&mut self, addr: u64, value: u16) -> Result<()> {
+        let buf = if self.is_big_endian()? {
+            value.to_be_bytes()
+        } else {
+            value.to_le_bytes()
+        };
+        self.write_bytes(addr, &buf)
+    }
+
+    pub fn write_u32(&mut self, addr: u64, value: u32) -> Result<()> {
+        let buf = if self.is_big_endian()? {
+            value.to_be_bytes()
+        } else {
+            value.to_le_bytes()
+        };
+        self.write_bytes(addr, &buf)
+    }
+
+    pub fn write_u64(&mut self, addr: u64, value: u64) -> Result<()> {
+        let buf = if self.is_big_endian()? {
+            value.to_be_bytes()
+        } else {
+            value.to_le_bytes()
+        };
+        self.write_bytes(addr, &buf)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::util::{decode_hex, encode_hex};
+    use crate::R2Pipe;
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    /// Emulates `p8`, `wx` and `cfg.bigendian` over `size` bytes at 0,
+    /// recording the commands received.
+    fn fake_memory(size: usize, big_endian: bool) -> (R2Pipe, Rc<RefCell<Vec<String>>>) {
+        let log = Rc::new(RefCell::new(Vec::new()));
+        let cmds = log.clone();
+        let mut mem = vec![0u8; size];
+        let r2 = R2Pipe::from_fn(move |cmd| {
+            cmds.borrow_mut().push(cmd.to_string());
+            let addr_of = |at: &str| usize::from_str_radix(&at[2..], 16).unwrap();
+            match cmd.split_whitespace().collect::<Vec<_>>()[..] {
+                ["p8", n, "@", at] => {
+                    let (n, at) = (n.parse::<usize>().unwrap(), addr_of(at));
+                    encode_hex(&mem[at..at + n]) + "\n"
+                }
+                ["wx", hex, "@", at] => {
+                    let (bytes, at) = (decode_hex(hex).unwrap(), addr_of(at));
+                    mem[at..at + bytes.len()].copy_from_slice(&bytes);
+                    String::new()
+                }
+                ["e", "cfg.bigendian"] => format!("{}\n", big_endian),
+                _ => panic!("unexpected command {}", cmd),
+            }
+        });
+        (r2, log)
+    }
+
+    #[test]
+    fn chunked_transfers() {
+        let (mut r2, log) = fake_memory(0x10010, false);
+        let data: Vec<u8> = (0..0x10008).map(|i| i as u8).collect();
+        r2.write_bytes(4, &data).unwrap();
+        assert_eq!(r2.read_bytes(4, data.len() as u64).unwrap(), data);
+        let log = log.borrow();
+        assert_eq!(log.len(), 4);
+        assert!(log[1].starts_with("wx ") && log[1].ends_with("@ 0x10004"));
+        assert_eq!(log[2], "p8 65536 @ 0x4");
+        assert_eq!(log[3], "p8 8 @ 0x10004");
+    }
+
+    #[test]
+    fn endianness() {
+        let (mut r2, _) = fake_memory(16, false);
+        r2.write_u32(0, 0x11223344).unwrap();
+        assert_eq!(r2.read_bytes(0, 4).unwrap(), [0x44, 0x33, 0x22, 0x11]);
+        assert_eq!(r2.read_u16(0).unwrap(), 0x3344);
+
+        let (mut r2, _) = fake_memory(16, true);
+        r2.write_u32(0, 0x11223344).unwrap();
+        assert_eq!(r2.read_bytes(0, 4).unwrap(), [0x11, 0x22, 0x33, 0x44]);
+        assert_eq!(r2.read_u64(0).unwrap(), 0x1122334400000000);
+    }
+
+    #[test]
+    fn cstrings() {
+        let (mut r2, _) = fake_memory(1024, false);
+        // crosses the boundary between two reads
+        let long = "a".repeat(300);
+        r2.write_bytes(16, long.as_bytes()).unwrap();
+        assert_eq!(r2.read_cstring(16).unwrap(), long);
+        // Latin-1 text is not valid UTF-8
+        r2.write_bytes(512, b"caf\xe9\0").unwrap();
+        assert_eq!(r2.read_cstring(512).unwrap(), "caf\u{fffd}");
+    }
+}
@@ -0,0 +1,120 @@
+//! Project management on top of the `P` family of commands.
+//!
+//! r2 reports project errors on stderr only, so every operation checks its
+//! outcome afterwards and fails with `Error::CommandFailed` when the project
+//! was not saved, opened or deleted.
+
+use crate::util::check_name;
+use crate::{Error, R2Pipe, Result};
+
+impl R2Pipe {
+    /// Sets the directory where projects are saved and loaded from
+    /// (`dir.projects`) in a running session. Spawned sessions can set it
+    /// up front with `R2PipeSpawnOptions::projects_dir`.
+    pub fn set_projects_dir(&mut self, dir: &str) -> Result<()> {
+        self.config_set("dir.projects", dir)
+    }
+
+    /// Saves the current session as project `name` (`Ps`), overwriting any
+    /// previous project with the same name.
+    pub fn save_project(&mut self, name: &str) -> Result<()> {
+        let name = check_name(name)?;
+        // r2 only sets `prj.name` once the project is written, so clear it
+        // first to notice an overwrite of the open project failing
+        let previous: String = self.config_get("prj.name")?;
+        self.config_set("prj.name", "")?;
+        self.cmd(&format!("Ps {}", name))?;
+        if self.config_get::<String>("prj.name")? != name {
+            self.config_set("prj.name", previous)?;
+            return Err(Error::CommandFailed(format!(
+                "cannot save project {}",
+                name
+            )));
+        }
+        Ok(())
+    }
+
+    /// Opens project `name` (`Po`).
+    pub fn open_project(&mut self, name: &str) -> Result<()> {
+        let name = check_name(name)?;
+        self.cmd(&format!("Po {}", name))?;
+        if self.config_get::<String>("prj.name")? != name {
+            return Err(Error::CommandFailed(format!(
+                "cannot open project {}",
+                name
+            )));
+        }
+        Ok(())
+    }
+
+    /// Lists the projects found in `dir.projects` (`Plj`).
+    pub fn list_projects(&mut self) -> Result<Vec<String>> {
+        self.cmdj_or_empty("Plj")
+    }
+
+    /// Deletes project `name` (`Pd`).
+    pub fn delete_project(&mut self, name: &str) -> Result<()> {
+        let name = check_name(name)?;
+        if !self.list_projects()?.iter().any(|p| p == name) {
+            return Err(Error::CommandFailed(format!("no such project {}", name)));
+        }
+        self.cmd(&format!("Pd {}", name))?;
+        if self.list_projects()?.iter().any(|p| p == name) {
+            return Err(Error::CommandFailed(format!(
+                "cannot delete project {}",
+                name
+            )));
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::R2Pipe;
+    #[cfg(not(windows))]
+    use crate::R2PipeSpawnOptions;
+
+    #[test]
+    fn failed_saves() {
+        // `Ps` of a read-only project fails without touching `prj.name`
+        let mut prj_name = "old".to_string();
+        let mut r2 = R2Pipe::from_fn(move |cmd| {
+            if let Some(value) = cmd.strip_prefix("\"\"e prj.name=") {
+                prj_name = value.to_string();
+            } else if let Some(name) = cmd.strip_prefix("Ps ") {
+                if name != "readonly" {
+                    prj_name = name.to_string();
+                }
+            } else {
+                assert_eq!(cmd, "e prj.name");
+                return format!("{}\n", prj_name);
+            }
+            String::new()
+        });
+        r2.save_project("old").unwrap();
+        assert!(r2.save_project("readonly").is_err());
+        assert_eq!(r2.config_get::<String>("prj.name").unwrap(), "old");
+    }
+
+    #[test]
+    #[cfg(not(windows))]
+    fn project_test() {
+        let dir = std::env::temp_dir().join(format!("r2pipe-projects-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let opts = R2PipeSpawnOptions {
+            projects_dir: Some(dir.to_string_lossy().into_owned()),
+            ..Default::default()
+        };
+        let mut pipe = R2Pipe::spawn("/bin/ls", Some(opts)).unwrap();
+        pipe.save_project("r2pipe_test").unwrap();
+        // saving again overwrites the project
+        pipe.save_project("r2pipe_test").unwrap();
+        assert_eq!(pipe.list_projects().unwrap(), vec!["r2pipe_test"]);
+        pipe.open_project("r2pipe_test").unwrap();
+        pipe.delete_project("r2pipe_test").unwrap();
+        assert!(pipe.delete_project("r2pipe_test").is_err());
+        pipe.close();
+        let _ = std::fs::remove_dir_all(dir);
+    }
+}
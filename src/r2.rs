@@ -13,7 +13,12 @@
 
 use crate::{r2pipe::R2Pipe, Error, Result};
 use serde_json::Value;
+use std::ops::{Deref, DerefMut};
 
+/// Higher-level handle over an `R2Pipe`.
+///
+/// The typed command wrappers implemented on `R2Pipe` (e.g.
+/// `disasm_at`) are reachable from `R2` as well through `Deref`.
 pub struct R2 {
     pipe: R2Pipe,
     readin: String,
@@ -82,3 +87,17 @@ impl R2 {
         self.readin = String::from("");
     }
 }
+
+impl Deref for R2 {
+    type Target = R2Pipe;
+
+    fn deref(&self) -> &R2Pipe {
+        &self.pipe
+    }
+}
+
+impl DerefMut for R2 {
+    fn deref_mut(&mut self) -> &mut R2Pipe {
+        &mut self.pipe
+    }
+}
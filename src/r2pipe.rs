@@ -3,12 +3,13 @@
 //! Please check crate level documentation for more details and example.
 
 use crate::dlfcn;
+use crate::stream::{JsonArray, ResponseReader};
 use crate::{Error, Result};
 
 use std::env;
 use std::fs::File;
 use std::io::prelude::*;
-use std::io::BufReader;
+use std::io::{BufReader, Cursor};
 use std::net::{SocketAddr, TcpStream, ToSocketAddrs};
 use std::path::Path;
 use std::process;
@@ -19,6 +20,7 @@ use std::sync::mpsc;
 use std::sync::Arc;
 use std::thread;
 
+use serde::de::DeserializeOwned;
 use serde_json::Value;
 
 /// File descriptors to the parent r2 process.
@@ -57,6 +59,8 @@ pub struct R2PipeThread {
 pub struct R2PipeSpawnOptions {
     pub exepath: String,
     pub args: Vec<&'static str>,
+    /// Directory where projects are saved and loaded from (`dir.projects`).
+    pub projects_dir: Option<String>,
 }
 
 impl Default for R2PipeSpawnOptions {
@@ -66,6 +70,7 @@ impl Default for R2PipeSpawnOptions {
         R2PipeSpawnOptions {
             exepath: exepath.to_string(),
             args: Vec::default(),
+            projects_dir: None,
         }
     }
 }
@@ -90,8 +95,41 @@ pub trait Pipe {
     fn callj(&mut self, cmd: &str) -> Result<Value> {
         self.cmdj(&format!("\"\"{}", cmd))
     }
+    /// Execute the command and return a reader over its output.
+    /// The default implementation buffers the whole response.
+    fn cmd_stream(&mut self, cmd: &str) -> Result<Box<dyn Read + '_>> {
+        Ok(Box::new(Cursor::new(self.cmd(cmd)?.into_bytes())))
+    }
     fn close(&mut self) {}
 }
+
+/// Line printed after each command of a batch.
+const BATCH_SEPARATOR: &str = "--";
+
+/// Splits the output of a batch of `count` commands, each followed by a
+/// separator line, into the output of each command.
+fn split_batch(out: &str, count: usize) -> Result<Vec<String>> {
+    let mut parts = Vec::with_capacity(count);
+    let mut cur = String::new();
+    for line in out.lines() {
+        if line == BATCH_SEPARATOR {
+            parts.push(std::mem::take(&mut cur));
+        } else {
+            cur.push_str(line);
+            cur.push('\n');
+        }
+    }
+    // output after the last separator means the responses are out of sync
+    if parts.len() != count || !cur.trim().is_empty() {
+        return Err(Error::CommandFailed(format!(
+            "expected {} responses, got {}",
+            count,
+            parts.len() + usize::from(!cur.trim().is_empty())
+        )));
+    }
+    Ok(parts)
+}
+
 fn getenv(k: &str) -> Option<i32> {
     match env::var(k) {
         Ok(val) => val.parse::<i32>().ok(),
@@ -160,9 +198,72 @@ impl R2Pipe {
         self.0.cmdj(cmd.trim())
     }
 
+    /// Execute the command and return a reader over its output, allowing
+    /// very large responses to be processed without buffering them whole.
+    ///
+    /// The pipe is borrowed until the reader is dropped. Dropping the reader
+    /// before the end of the output discards the remainder of the response.
+    pub fn cmd_stream(&mut self, cmd: &str) -> Result<impl Read + '_> {
+        self.0.cmd_stream(cmd.trim())
+    }
+
+    /// Execute a command producing a JSON array and iterate over its
+    /// elements one at a time, deserializing each of them into `T`.
+    pub fn cmdj_stream<T: DeserializeOwned>(
+        &mut self,
+        cmd: &str,
+    ) -> Result<JsonArray<impl Read + '_, T>> {
+        Ok(JsonArray::new(self.cmd_stream(cmd)?))
+    }
+
     pub fn close(&mut self) {
         self.0.close();
     }
+
+    /// Runs a command producing a JSON array, treating an empty response
+    /// as an empty list.
+    pub(crate) fn cmdj_or_empty<T: DeserializeOwned>(&mut self, cmd: &str) -> Result<Vec<T>> {
+        match self.cmdj(cmd) {
+            Ok(v) => Ok(serde_json::from_value(v)?),
+            Err(Error::EmptyResponse) => Ok(Vec::new()),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Runs several commands in as few round-trips as possible, returning
+    /// the output of each command.
+    pub(crate) fn cmd_batch(&mut self, cmds: &[String]) -> Result<Vec<String>> {
+        const BATCH_SIZE: usize = 256;
+
+        let mut res = Vec::with_capacity(cmds.len());
+        for batch in cmds.chunks(BATCH_SIZE) {
+            let cmd: Vec<String> = batch
+                .iter()
+                .map(|c| format!("{};?e {}", c, BATCH_SEPARATOR))
+                .collect();
+            let out = self.cmd(&cmd.join(";"))?;
+            res.extend(split_batch(&out, batch.len())?);
+        }
+        Ok(res)
+    }
+
+    /// Runs several commands producing JSON arrays in a single round-trip,
+    /// returning one list per command. Empty responses become empty lists.
+    pub(crate) fn cmdj_batch<T: DeserializeOwned>(
+        &mut self,
+        cmds: &[String],
+    ) -> Result<Vec<Vec<T>>> {
+        let mut res = Vec::with_capacity(cmds.len());
+        for part in self.cmd_batch(cmds)? {
+            let part = part.trim();
+            res.push(if part.is_empty() {
+                Vec::new()
+            } else {
+                serde_json::from_str(part)?
+            });
+        }
+        Ok(res)
+    }
     /// Escape the command before executing, valid only as of r2 v.5.8.0 "icebucket"
     pub fn call(&mut self, cmd: &str) -> Result<String> {
         self.0.call(cmd)
@@ -193,11 +294,19 @@ impl R2Pipe {
             return R2Pipe::open();
         }
 
-        let R2PipeSpawnOptions { exepath, args } = opts.take().unwrap_or_default();
+        let R2PipeSpawnOptions {
+            exepath,
+            args,
+            projects_dir,
+        } = opts.take().unwrap_or_default();
 
         let path = Path::new(name.as_ref());
-        let mut child = Command::new(exepath)
-            .arg("-q0")
+        let mut command = Command::new(exepath);
+        command.arg("-q0");
+        if let Some(dir) = projects_dir {
+            command.arg("-e").arg(format!("dir.projects={}", dir));
+        }
+        let mut child = command
             .args(&args)
             .arg(path)
             .stdin(Stdio::piped())
@@ -309,6 +418,12 @@ impl Pipe for R2PipeSpawn {
         process_result(res)
     }
 
+    fn cmd_stream(&mut self, cmd: &str) -> Result<Box<dyn Read + '_>> {
+        let cmd = cmd.to_owned() + "\n";
+        self.write.write_all(cmd.as_bytes())?;
+        Ok(Box::new(ResponseReader::new(&mut self.read)))
+    }
+
     fn close(&mut self) {
         let _ = self.cmd("q!");
         if let Some(child) = &mut self.child {
@@ -334,19 +449,28 @@ impl Pipe for R2PipeLang {
         self.read.read_until(0u8, &mut res)?;
         process_result(res)
     }
+
+    fn cmd_stream(&mut self, cmd: &str) -> Result<Box<dyn Read + '_>> {
+        self.write.write_all(cmd.as_bytes())?;
+        Ok(Box::new(ResponseReader::new(&mut self.read)))
+    }
+}
+
+impl R2PipeHttp {
+    /// Connects to the server and sends the request for `cmd`.
+    fn request(&self, cmd: &str) -> Result<TcpStream> {
+        let host = self.host.strip_prefix("http://").unwrap_or(&self.host);
+        let mut stream = TcpStream::connect(host)?;
+        let req = format!("GET /cmd/{} HTTP/1.1\r\nHost: {}\r\n\r\n", cmd, host);
+        stream.write_all(req.as_bytes())?;
+        Ok(stream)
+    }
 }
 
 impl Pipe for R2PipeHttp {
     fn cmd(&mut self, cmd: &str) -> Result<String> {
-        let host = if self.host.starts_with("http://") {
-            &self.host[7..]
-        } else {
-            &self.host
-        };
-        let mut stream = TcpStream::connect(host)?;
-        let req = format!("GET /cmd/{} HTTP/1.1\r\n", cmd);
+        let mut stream = self.request(cmd)?;
         let mut resp = Vec::with_capacity(1024);
-        stream.write_all(req.as_bytes())?;
         stream.read_to_end(&mut resp)?;
 
         // index of the start of response body
@@ -358,6 +482,18 @@ impl Pipe for R2PipeHttp {
 
         Ok(str::from_utf8(&resp[index..]).map(|s| s.to_string())?)
     }
+
+    fn cmd_stream(&mut self, cmd: &str) -> Result<Box<dyn Read + '_>> {
+        let stream = self.request(cmd)?;
+
+        // skip the headers, the body follows the first empty line
+        let mut reader = BufReader::new(stream);
+        let mut line = String::new();
+        while reader.read_line(&mut line)? > 0 && line != "\r\n" {
+            line.clear();
+        }
+        Ok(Box::new(reader))
+    }
 }
 
 impl Pipe for R2PipeTcp {
@@ -369,6 +505,12 @@ impl Pipe for R2PipeTcp {
         res.push(0);
         process_result(res)
     }
+
+    fn cmd_stream(&mut self, cmd: &str) -> Result<Box<dyn Read + '_>> {
+        let mut stream = TcpStream::connect(self.socket_addr)?;
+        stream.write_all(cmd.as_bytes())?;
+        Ok(Box::new(BufReader::new(stream)))
+    }
 }
 
 pub struct R2PipeNative {
@@ -421,8 +563,35 @@ impl Drop for R2PipeNative {
     }
 }
 
+/// Pipe answering every command through a closure, so the wrappers can be
+/// tested without an r2 installation.
+#[cfg(test)]
+struct FnPipe<F>(F);
+
+#[cfg(test)]
+impl<F: FnMut(&str) -> Result<String>> Pipe for FnPipe<F> {
+    fn cmd(&mut self, cmd: &str) -> Result<String> {
+        (self.0)(cmd)
+    }
+}
+
+#[cfg(test)]
+impl R2Pipe {
+    pub(crate) fn from_fn<F: FnMut(&str) -> String + 'static>(mut f: F) -> R2Pipe {
+        R2Pipe::from_fallible_fn(move |cmd| Ok(f(cmd)))
+    }
+
+    pub(crate) fn from_fallible_fn<F>(f: F) -> R2Pipe
+    where
+        F: FnMut(&str) -> Result<String> + 'static,
+    {
+        R2Pipe(Box::new(FnPipe(f)))
+    }
+}
+
 #[cfg(test)]
 mod test {
+    use super::split_batch;
     use super::Pipe;
     use super::R2PipeNative;
     #[cfg(not(windows))]
@@ -440,4 +609,18 @@ mod test {
         let mut r2p = R2PipeNative::open("malloc://32").unwrap();
         assert_eq!("a\n", r2p.cmd("echo a").unwrap());
     }
+
+    #[test]
+    fn batch_split() {
+        let out = "[1]\n--\n--\n[\n  2,\n  3\n]\n--\n";
+        let parts = split_batch(out, 3).unwrap();
+        assert_eq!(parts[0], "[1]\n");
+        assert!(parts[1].is_empty());
+        assert_eq!(parts[2], "[\n  2,\n  3\n]\n");
+        assert!(split_batch(out, 2).is_err());
+        assert!(split_batch(out, 4).is_err());
+        // output left after the last separator
+        assert!(split_batch("[1]\n--\n[2]\n", 1).is_err());
+        assert_eq!(split_batch("", 0).unwrap().len(), 0);
+    }
 }
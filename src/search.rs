@@ -0,0 +1,205 @@
+//! Typed wrappers for the `/` family of search commands.
+//!
+//! Search ranges are given through `SearchOptions`, which temporarily sets
+//! `search.in`, `search.from` and `search.to`. The previous values are
+//! restored once the search is done, even if it failed.
+
+use crate::util::check_single_line;
+use crate::{Error, R2Pipe, Result};
+
+use serde_json::Value;
+
+/// A single search result.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SearchHit {
+    pub addr: u64,
+    pub size: u64,
+    /// Kind of hit as reported by r2, e.g. `hexpair`, `string` or `rop`.
+    pub kind: String,
+    /// Matched data: hex pairs, text, or `;`-separated instructions.
+    pub data: String,
+}
+
+/// Where to search. Fields left to `None` keep the current configuration.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SearchOptions {
+    /// Value for `search.in`, e.g. `io.maps`, `bin.sections` or `raw`.
+    pub search_in: Option<String>,
+    pub from: Option<u64>,
+    pub to: Option<u64>,
+}
+
+/// Kinds of cryptographic material r2 can look for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CryptoKind {
+    /// Expanded AES keys (`/ca`).
+    Aes,
+    /// RSA private keys (`/cr`).
+    Rsa,
+    /// DER certificates (`/cd`).
+    Certificate,
+}
+
+impl SearchHit {
+    fn from_json(hit: &Value, kind: &str) -> Option<SearchHit> {
+        let addr = hit["offset"].as_u64().or_else(|| hit["addr"].as_u64());
+        // rop gadgets only carry the offsets of their instructions
+        if let Some(ops) = hit["opcodes"].as_array() {
+            let code: Vec<&str> = ops.iter().filter_map(|o| o["opcode"].as_str()).collect();
+            return Some(SearchHit {
+                addr: ops.first().and_then(|o| o["offset"].as_u64()).or(addr)?,
+                size: hit["size"].as_u64().unwrap_or(0),
+                kind: "rop".to_string(),
+                data: code.join("; "),
+            });
+        }
+        let data = hit["data"]
+            .as_str()
+            .or_else(|| hit["code"].as_str())
+            .unwrap_or("")
+            .to_string();
+        let kind = hit["type"].as_str().unwrap_or(kind).to_string();
+        let size = hit["len"]
+            .as_u64()
+            .or_else(|| hit["size"].as_u64())
+            .unwrap_or(match kind.as_str() {
+                "hexpair" => data.len() as u64 / 2,
+                "string" => data.len() as u64,
+                _ => 0,
+            });
+        Some(SearchHit {
+            addr: addr?,
+            size,
+            kind,
+            data,
+        })
+    }
+}
+
+impl R2Pipe {
+    /// Searches for hex pairs (`/x`), with an optional mask of the same
+    /// length where `0` bits are ignored.
+    pub fn search_hex(
+        &mut self,
+        hex: &str,
+        mask: Option<&str>,
+        opts: &SearchOptions,
+    ) -> Result<Vec<SearchHit>> {
+        let is_hex = |s: &str| !s.is_empty() && s.chars().all(|c| c.is_ascii_hexdigit());
+        if !is_hex(hex) || !mask.map_or(true, is_hex) {
+            return Err(Error::InvalidArgument(format!(
+                "invalid hex pattern {}",
+                hex
+            )));
+        }
+        let cmd = match mask {
+            Some(mask) => format!("/xj {}:{}", hex, mask),
+            None => format!("/xj {}", hex),
+        };
+        self.search(&cmd, "hexpair", opts)
+    }
+
+    /// Searches for a string (`/`).
+    pub fn search_string(&mut self, text: &str, opts: &SearchOptions) -> Result<Vec<SearchHit>> {
+        let cmd = format!("\"\"/j {}", check_single_line(text)?);
+        self.search(&cmd, "string", opts)
+    }
+
+    /// Searches for assembled instructions (`/a`), separated by `;`.
+    pub fn search_asm(&mut self, code: &str, opts: &SearchOptions) -> Result<Vec<SearchHit>> {
+        let cmd = format!("\"\"/aj {}", check_single_line(code)?);
+        self.search(&cmd, "asm", opts)
+    }
+
+    /// Searches for ROP gadgets (`/R`), optionally containing `filter`.
+    pub fn search_rop(
+        &mut self,
+        filter: Option<&str>,
+        opts: &SearchOptions,
+    ) -> Result<Vec<SearchHit>> {
+        let cmd = match filter {
+            Some(f) => format!("\"\"/Rj {}", check_single_line(f)?),
+            None => "/Rj".to_string(),
+        };
+        self.search(&cmd, "rop", opts)
+    }
+
+    /// Searches for cryptographic keys and certificates (`/c`).
+    pub fn search_crypto(
+        &mut self,
+        kind: CryptoKind,
+        opts: &SearchOptions,
+    ) -> Result<Vec<SearchHit>> {
+        let cmd = match kind {
+            CryptoKind::Aes => "/caj",
+            CryptoKind::Rsa => "/crj",
+            CryptoKind::Certificate => "/cdj",
+        };
+        self.search(cmd, "crypto", opts)
+    }
+
+    /// Searches for an integer `value` of `width` bytes (1, 2, 4 or 8)
+    /// encoded with the current endianness (`/v`).
+    pub fn search_value(
+        &mut self,
+        value: u64,
+        width: u8,
+        opts: &SearchOptions,
+    ) -> Result<Vec<SearchHit>> {
+        if ![1, 2, 4, 8].contains(&width) {
+            return Err(Error::InvalidArgument(format!(
+                "invalid value width {}",
+                width
+            )));
+        }
+        self.search(&format!("/v{}j {:#x}", width, value), "value", opts)
+    }
+
+    fn search(&mut self, cmd: &str, kind: &str, opts: &SearchOptions) -> Result<Vec<SearchHit>> {
+        let from = opts.from.map(|a| format!("{:#x}", a));
+        let to = opts.to.map(|a| format!("{:#x}", a));
+        let mut overrides = Vec::new();
+        if let Some(search_in) = &opts.search_in {
+            overrides.push(("search.in", search_in.as_str()));
+        }
+        if let Some(from) = &from {
+            overrides.push(("search.from", from.as_str()));
+        }
+        if let Some(to) = &to {
+            overrides.push(("search.to", to.as_str()));
+        }
+
+        let res: Vec<Value> = self.with_config(&overrides, |r2| r2.cmdj_or_empty(cmd))?;
+        Ok(res
+            .iter()
+            .filter_map(|hit| SearchHit::from_json(hit, kind))
+            .collect())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::SearchHit;
+    use serde_json::json;
+
+    #[test]
+    fn hits_from_json() {
+        let xj = json!({"offset": 4194304, "type": "hexpair", "data": "7f454c46"});
+        let hit = SearchHit::from_json(&xj, "hexpair").unwrap();
+        assert_eq!((hit.addr, hit.size), (0x400000, 4));
+
+        let string = json!({"offset": 4096, "type": "string", "data": "hello"});
+        assert_eq!(SearchHit::from_json(&string, "string").unwrap().size, 5);
+
+        let asm = json!({"offset": 8192, "code": "nop"});
+        assert_eq!(SearchHit::from_json(&asm, "asm").unwrap().size, 0);
+
+        let rj = json!({"opcodes": [
+            {"offset": 4198400, "size": 1, "opcode": "pop rdi", "type": "pop"},
+            {"offset": 4198401, "size": 1, "opcode": "ret", "type": "ret"}
+        ], "retaddr": 4198401, "size": 2});
+        let hit = SearchHit::from_json(&rj, "rop").unwrap();
+        assert_eq!((hit.addr, hit.size), (0x401000, 2));
+        assert_eq!(hit.data, "pop rdi; ret");
+    }
+}
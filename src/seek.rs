@@ -0,0 +1,127 @@
+//! Current offset and block size management.
+//!
+//! `SeekGuard` records the offset and block size when created and restores
+//! them when dropped, so helpers can move around freely without disturbing
+//! the commands issued after them.
+
+use crate::util::parse_u64;
+use crate::{Error, R2Pipe, Result};
+
+use std::ops::{Deref, DerefMut};
+
+impl R2Pipe {
+    /// Returns the current offset (`s`).
+    pub fn seek(&mut self) -> Result<u64> {
+        let out = self.cmd("s")?;
+        parse_u64(&out).ok_or_else(|| Error::CommandFailed(format!("invalid offset {:?}", out)))
+    }
+
+    /// Moves the current offset to `addr`.
+    pub fn seek_to(&mut self, addr: u64) -> Result<()> {
+        self.cmd(&format!("s {:#x}", addr))?;
+        Ok(())
+    }
+
+    /// Returns the current block size (`b`).
+    pub fn block_size(&mut self) -> Result<u64> {
+        let out = self.cmd("b")?;
+        parse_u64(&out).ok_or_else(|| Error::CommandFailed(format!("invalid block size {:?}", out)))
+    }
+
+    pub fn set_block_size(&mut self, size: u64) -> Result<()> {
+        self.cmd(&format!("b {:#x}", size))?;
+        Ok(())
+    }
+
+    /// Saves the current offset and block size, which are restored when the
+    /// returned guard is dropped. The pipe remains usable through the guard.
+    pub fn seek_guard(&mut self) -> Result<SeekGuard<'_>> {
+        let offset = self.seek()?;
+        let block_size = self.block_size()?;
+        Ok(SeekGuard {
+            r2: self,
+            offset,
+            block_size,
+        })
+    }
+}
+
+/// Restores the offset and block size of an `R2Pipe` on drop.
+pub struct SeekGuard<'a> {
+    r2: &'a mut R2Pipe,
+    offset: u64,
+    block_size: u64,
+}
+
+impl SeekGuard<'_> {
+    /// Offset that will be restored.
+    pub fn saved_offset(&self) -> u64 {
+        self.offset
+    }
+
+    /// Block size that will be restored.
+    pub fn saved_block_size(&self) -> u64 {
+        self.block_size
+    }
+}
+
+impl Deref for SeekGuard<'_> {
+    type Target = R2Pipe;
+
+    fn deref(&self) -> &R2Pipe {
+        self.r2
+    }
+}
+
+impl DerefMut for SeekGuard<'_> {
+    fn deref_mut(&mut self) -> &mut R2Pipe {
+        self.r2
+    }
+}
+
+impl Drop for SeekGuard<'_> {
+    fn drop(&mut self) {
+        let _ = self.r2.set_block_size(self.block_size);
+        let _ = self.r2.seek_to(self.offset);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{Error, R2Pipe};
+    use std::cell::Cell;
+    use std::rc::Rc;
+
+    #[test]
+    fn guard_restores() {
+        let state = Rc::new(Cell::new((0x1000u64, 0x100u64)));
+        let fake = state.clone();
+        let mut r2 = R2Pipe::from_fallible_fn(move |cmd| {
+            let (offset, block_size) = fake.get();
+            let parse = |v: &str| u64::from_str_radix(&v[2..], 16).unwrap();
+            match cmd.split_once(' ') {
+                None if cmd == "s" => Ok(format!("{:#x}\n", offset)),
+                None if cmd == "b" => Ok(format!("{:#x}\n", block_size)),
+                Some(("s", v)) => {
+                    fake.set((parse(v), block_size));
+                    Ok(String::new())
+                }
+                Some(("b", v)) => {
+                    fake.set((offset, parse(v)));
+                    Ok(String::new())
+                }
+                _ => Err(Error::CommandFailed(cmd.to_string())),
+            }
+        });
+        {
+            let mut guard = r2.seek_guard().unwrap();
+            guard.seek_to(0x2000).unwrap();
+            guard.set_block_size(0x20).unwrap();
+            assert_eq!(guard.seek().unwrap(), 0x2000);
+            assert_eq!(guard.saved_offset(), 0x1000);
+            // a failing command does not prevent the restore
+            assert!(guard.cmd("pd 1").is_err());
+        }
+        assert_eq!(state.get(), (0x1000, 0x100));
+    }
+}
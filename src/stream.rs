@@ -0,0 +1,228 @@
+//! Incremental readers for large command responses.
+//!
+//! Commands such as `pdj 100000` or `izzj` on big firmware images can
+//! produce hundreds of megabytes of output. The types in this module let the
+//! caller consume such responses piecewise instead of buffering them whole.
+
+use crate::{Error, Result};
+
+use std::io::prelude::*;
+use std::io::BufReader;
+use std::marker::PhantomData;
+
+use serde::de::DeserializeOwned;
+use serde_json::Value;
+
+/// Reads a single NUL-terminated response from a pipe.
+///
+/// Reading stops at the terminator, which is consumed but never returned.
+/// If the reader is dropped early the rest of the response is drained so the
+/// pipe stays in sync for the next command.
+pub(crate) struct ResponseReader<'a, R: BufRead> {
+    inner: &'a mut R,
+    done: bool,
+}
+
+impl<'a, R: BufRead> ResponseReader<'a, R> {
+    pub(crate) fn new(inner: &'a mut R) -> ResponseReader<'a, R> {
+        ResponseReader { inner, done: false }
+    }
+}
+
+impl<R: BufRead> Read for ResponseReader<'_, R> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        if self.done || buf.is_empty() {
+            return Ok(0);
+        }
+        let available = self.inner.fill_buf()?;
+        if available.is_empty() {
+            self.done = true;
+            return Ok(0);
+        }
+        let len = match available.iter().position(|&b| b == 0) {
+            Some(0) => {
+                self.done = true;
+                self.inner.consume(1);
+                return Ok(0);
+            }
+            Some(pos) => pos.min(buf.len()),
+            None => available.len().min(buf.len()),
+        };
+        buf[..len].copy_from_slice(&available[..len]);
+        self.inner.consume(len);
+        Ok(len)
+    }
+}
+
+impl<R: BufRead> Drop for ResponseReader<'_, R> {
+    fn drop(&mut self) {
+        if !self.done {
+            let mut rest = Vec::new();
+            let _ = self.inner.read_until(0u8, &mut rest);
+        }
+    }
+}
+
+/// Iterator over the elements of a JSON array read from a stream.
+///
+/// Only one element is held in memory at a time, so arbitrarily large
+/// responses of `*j` commands can be processed with bounded memory.
+/// Elements are deserialized into `T`, which defaults to `serde_json::Value`.
+pub struct JsonArray<R: Read, T = Value> {
+    reader: BufReader<R>,
+    started: bool,
+    finished: bool,
+    marker: PhantomData<T>,
+}
+
+impl<R: Read, T: DeserializeOwned> JsonArray<R, T> {
+    pub fn new(reader: R) -> JsonArray<R, T> {
+        JsonArray {
+            reader: BufReader::new(reader),
+            started: false,
+            finished: false,
+            marker: PhantomData,
+        }
+    }
+
+    fn next_byte(&mut self) -> Result<Option<u8>> {
+        let b = self.reader.fill_buf()?.first().copied();
+        if b.is_some() {
+            self.reader.consume(1);
+        }
+        Ok(b)
+    }
+
+    fn next_non_ws(&mut self) -> Result<Option<u8>> {
+        while let Some(b) = self.next_byte()? {
+            if !b.is_ascii_whitespace() {
+                return Ok(Some(b));
+            }
+        }
+        Ok(None)
+    }
+
+    /// Collects the raw bytes of the next element, up to the `,` or `]`
+    /// that follows it at the top level of the array.
+    fn read_element(&mut self) -> Result<Option<Vec<u8>>> {
+        if !self.started {
+            self.started = true;
+            match self.next_non_ws()? {
+                Some(b'[') => {}
+                // commands print nothing at all when there is nothing to list
+                None => return Ok(None),
+                Some(b) => {
+                    return Err(Error::CommandFailed(format!(
+                        "expected a JSON array, found {:?}",
+                        b as char
+                    )))
+                }
+            }
+        }
+        let mut elem = Vec::new();
+        let (mut depth, mut in_string, mut escaped) = (0usize, false, false);
+        while let Some(b) = self.next_byte()? {
+            if in_string {
+                elem.push(b);
+                if escaped {
+                    escaped = false;
+                } else if b == b'\\' {
+                    escaped = true;
+                } else if b == b'"' {
+                    in_string = false;
+                }
+                continue;
+            }
+            match b {
+                b'"' => in_string = true,
+                b'{' | b'[' => depth += 1,
+                b'}' if depth > 0 => depth -= 1,
+                b']' if depth > 0 => depth -= 1,
+                b',' if depth == 0 => return Ok(Some(elem)),
+                b']' if depth == 0 => {
+                    self.finished = true;
+                    let empty = elem.iter().all(u8::is_ascii_whitespace);
+                    return Ok(if empty { None } else { Some(elem) });
+                }
+                _ => {}
+            }
+            elem.push(b);
+        }
+        Err(Error::EmptyResponse)
+    }
+}
+
+impl<R: Read, T: DeserializeOwned> Iterator for JsonArray<R, T> {
+    type Item = Result<T>;
+
+    fn next(&mut self) -> Option<Result<T>> {
+        if self.finished {
+            return None;
+        }
+        match self.read_element() {
+            Ok(Some(elem)) => Some(serde_json::from_slice(&elem).map_err(Error::from)),
+            Ok(None) => {
+                self.finished = true;
+                None
+            }
+            Err(e) => {
+                self.finished = true;
+                Some(Err(e))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{JsonArray, ResponseReader};
+    use crate::Error;
+    use serde_json::Value;
+    use std::io::{BufReader, Read};
+
+    #[test]
+    fn response_reader_stops_at_nul() {
+        let mut input = BufReader::with_capacity(4, &b"hello world\n\0partial\0tail"[..]);
+        let mut out = String::new();
+        ResponseReader::new(&mut input)
+            .read_to_string(&mut out)
+            .unwrap();
+        assert_eq!(out, "hello world\n");
+
+        // dropping a partially read response drains it up to the terminator
+        let mut byte = [0; 1];
+        ResponseReader::new(&mut input)
+            .read_exact(&mut byte)
+            .unwrap();
+        out.clear();
+        input.read_to_string(&mut out).unwrap();
+        assert_eq!(out, "tail");
+    }
+
+    #[test]
+    fn json_array_elements() {
+        let input = br#" [{"a":"x,]"},[1,2], 3 ,"s\"]"]"#;
+        let elems: Vec<Value> = JsonArray::new(&input[..])
+            .collect::<crate::Result<_>>()
+            .unwrap();
+        assert_eq!(elems.len(), 4);
+        assert_eq!(elems[0]["a"], "x,]");
+        assert_eq!(elems[1][1], 2);
+        assert_eq!(elems[2], 3);
+        assert_eq!(elems[3], "s\"]");
+        let empty: Vec<Value> = JsonArray::new(&b"[]"[..])
+            .collect::<crate::Result<_>>()
+            .unwrap();
+        assert!(empty.is_empty());
+    }
+
+    #[test]
+    fn json_array_empty_or_invalid() {
+        // empty listings print nothing at all
+        assert_eq!(JsonArray::<_, Value>::new(&b""[..]).count(), 0);
+        assert_eq!(JsonArray::<_, Value>::new(&b"\n"[..]).count(), 0);
+        let mut it = JsonArray::<_, Value>::new(&br#"{"a":1}"#[..]);
+        assert!(matches!(it.next(), Some(Err(Error::CommandFailed(_)))));
+        assert!(it.next().is_none());
+    }
+}
@@ -0,0 +1,150 @@
+//! String extraction on top of `izj` and `izzj`.
+//!
+//! `izj` only looks at the data sections of the binary while `izzj` scans
+//! the whole file. For huge firmware images, `strings_stream` decodes the
+//! `izzj` output one entry at a time.
+
+use crate::{R2Pipe, Result};
+
+use serde::{Deserialize, Deserializer};
+use serde_derive::Deserialize;
+
+/// Encoding of a string found in the binary.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum StringEncoding {
+    Ascii,
+    Utf8,
+    Utf16le,
+    Utf16be,
+    Utf32le,
+    Utf32be,
+    /// Any other encoding reported by r2.
+    Other(String),
+}
+
+impl From<&str> for StringEncoding {
+    fn from(kind: &str) -> StringEncoding {
+        match kind {
+            "ascii" => StringEncoding::Ascii,
+            "utf8" => StringEncoding::Utf8,
+            "utf16le" | "wide" => StringEncoding::Utf16le,
+            "utf16be" | "wide_be" => StringEncoding::Utf16be,
+            "utf32le" | "wide32" => StringEncoding::Utf32le,
+            "utf32be" | "wide32_be" => StringEncoding::Utf32be,
+            other => StringEncoding::Other(other.to_string()),
+        }
+    }
+}
+
+fn deserialize_encoding<'de, D>(deserializer: D) -> std::result::Result<StringEncoding, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    Ok(StringEncoding::from(
+        String::deserialize(deserializer)?.as_str(),
+    ))
+}
+
+/// A string found in the binary.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub struct StringEntry {
+    pub vaddr: u64,
+    pub paddr: u64,
+    #[serde(default)]
+    pub ordinal: u64,
+    /// Size in bytes.
+    #[serde(default)]
+    pub size: u64,
+    /// Length in characters.
+    #[serde(default)]
+    pub length: u64,
+    #[serde(default)]
+    pub section: String,
+    #[serde(rename = "type", deserialize_with = "deserialize_encoding")]
+    pub encoding: StringEncoding,
+    /// The decoded text.
+    #[serde(rename = "string")]
+    pub text: String,
+}
+
+/// Criteria strings must match to be returned.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct StringFilter {
+    /// Minimum length in characters.
+    pub min_length: u64,
+    /// Accepted encodings, all of them if empty.
+    pub encodings: Vec<StringEncoding>,
+    /// Only keep strings located in this section.
+    pub section: Option<String>,
+}
+
+impl StringFilter {
+    pub fn matches(&self, s: &StringEntry) -> bool {
+        s.length >= self.min_length
+            && (self.encodings.is_empty() || self.encodings.contains(&s.encoding))
+            && self.section.as_ref().map_or(true, |sec| *sec == s.section)
+    }
+}
+
+impl R2Pipe {
+    /// Strings in the data sections of the binary (`izj`).
+    pub fn strings(&mut self, filter: &StringFilter) -> Result<Vec<StringEntry>> {
+        let mut res: Vec<StringEntry> = self.cmdj_or_empty("izj")?;
+        res.retain(|s| filter.matches(s));
+        Ok(res)
+    }
+
+    /// Strings in the whole binary (`izzj`).
+    pub fn strings_whole_binary(&mut self, filter: &StringFilter) -> Result<Vec<StringEntry>> {
+        self.strings_stream(filter.clone())?.collect()
+    }
+
+    /// Strings in the whole binary, decoded one at a time from the `izzj`
+    /// output so that memory usage stays bounded.
+    pub fn strings_stream(
+        &mut self,
+        filter: StringFilter,
+    ) -> Result<impl Iterator<Item = Result<StringEntry>> + '_> {
+        let stream = self.cmdj_stream::<StringEntry>("izzj")?;
+        Ok(stream.filter(move |s| s.as_ref().map_or(true, |s| filter.matches(s))))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{StringEncoding, StringEntry, StringFilter};
+    use serde_json::json;
+
+    #[test]
+    fn entries_from_izj() {
+        let izj = json!([
+            {"vaddr": 4202500, "paddr": 8196, "ordinal": 0, "size": 6, "length": 5,
+             "section": ".rodata", "type": "ascii", "string": "hello"},
+            {"vaddr": 4202512, "paddr": 8208, "ordinal": 1, "size": 12, "length": 5,
+             "section": ".rodata", "type": "utf16le", "string": "wide!"},
+            {"vaddr": 4202530, "paddr": 8226, "ordinal": 2, "size": 8, "length": 3,
+             "section": ".data", "type": "wide", "string": "old"}
+        ]);
+        let entries: Vec<StringEntry> = serde_json::from_value(izj).unwrap();
+        let encodings: Vec<_> = entries.iter().map(|s| s.encoding.clone()).collect();
+        assert_eq!(
+            encodings,
+            [
+                StringEncoding::Ascii,
+                StringEncoding::Utf16le,
+                StringEncoding::Utf16le
+            ]
+        );
+        assert_eq!(entries[0].text, "hello");
+
+        let filter = StringFilter {
+            min_length: 4,
+            encodings: vec![StringEncoding::Utf16le],
+            section: Some(".rodata".to_string()),
+        };
+        let kept: Vec<_> = entries.iter().filter(|s| filter.matches(s)).collect();
+        assert_eq!(kept.len(), 1);
+        assert_eq!(kept[0].text, "wide!");
+        assert!(entries.iter().all(|s| StringFilter::default().matches(s)));
+    }
+}
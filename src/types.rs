@@ -0,0 +1,393 @@
+//! Type management on top of the `t` family of commands.
+//!
+//! C declarations can be loaded from a string (`td`) or a header file
+//! (`to`), listed back as typed values and applied at an address, in which
+//! case the memory is decoded through the struct's `pf` format.
+
+use crate::util::{check_name, check_single_line};
+use crate::{Error, R2Pipe, Result};
+
+use serde_json::{Map, Value};
+
+/// A member of a struct or union.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Member {
+    pub name: String,
+    /// C type of the member, e.g. `int` or `char *`.
+    pub kind: String,
+    pub offset: Option<u64>,
+}
+
+/// A struct or union definition (`tsj`).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Struct {
+    pub name: String,
+    pub members: Vec<Member>,
+}
+
+/// An enum definition (`tej`).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Enum {
+    pub name: String,
+    pub values: Vec<(String, i64)>,
+}
+
+/// A typedef (`ttj`).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Typedef {
+    pub name: String,
+    pub kind: String,
+}
+
+/// An argument of a function signature.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Argument {
+    pub name: String,
+    pub kind: String,
+}
+
+/// A function signature (`tfj`).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FunctionType {
+    pub name: String,
+    pub ret: String,
+    pub args: Vec<Argument>,
+}
+
+/// A decoded field, as produced by `pfj`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Field {
+    pub name: String,
+    /// `pf` format character(s) of the field, e.g. `d` or `z`.
+    pub kind: String,
+    pub offset: u64,
+    /// Decoded value of a scalar field, `Null` for nested structs.
+    pub value: Value,
+    /// Fields of a nested struct.
+    pub fields: Vec<Field>,
+}
+
+impl Field {
+    pub(crate) fn from_pfj(item: &Value) -> Option<Field> {
+        let obj = item.as_object()?;
+        let value = obj.get("value").cloned().unwrap_or(Value::Null);
+        let fields = match &value {
+            Value::Array(items) if items.iter().any(is_field) => flatten_fields(items),
+            _ => Vec::new(),
+        };
+        Some(Field {
+            name: str_field(obj, "name"),
+            kind: str_field(obj, "type"),
+            offset: obj.get("offset").and_then(Value::as_u64).unwrap_or(0),
+            value: if fields.is_empty() {
+                value
+            } else {
+                Value::Null
+            },
+            fields,
+        })
+    }
+}
+
+fn is_field(v: &Value) -> bool {
+    v.get("name").is_some() || matches!(v, Value::Array(a) if a.iter().any(is_field))
+}
+
+/// Nested structs are reported either as a list of fields or as a list of
+/// lists of fields; both are flattened into a single list.
+pub(crate) fn flatten_fields(items: &[Value]) -> Vec<Field> {
+    let mut res = Vec::new();
+    for item in items {
+        match item {
+            Value::Array(inner) => res.extend(flatten_fields(inner)),
+            other => res.extend(Field::from_pfj(other)),
+        }
+    }
+    res
+}
+
+fn str_field(obj: &Map<String, Value>, key: &str) -> String {
+    match obj.get(key) {
+        Some(Value::String(s)) => s.clone(),
+        Some(Value::Null) | None => String::new(),
+        Some(other) => other.to_string(),
+    }
+}
+
+/// r2 versions disagree on whether listings are arrays of named objects or
+/// objects keyed by name; both are turned into `(name, body)` pairs.
+fn named_entries(v: Value) -> Vec<(String, Value)> {
+    match v {
+        Value::Array(items) => items
+            .into_iter()
+            .map(|item| {
+                let name = item
+                    .as_object()
+                    .map(|o| str_field(o, "name"))
+                    .unwrap_or_default();
+                (name, item)
+            })
+            .collect(),
+        Value::Object(map) => map.into_iter().collect(),
+        _ => Vec::new(),
+    }
+}
+
+/// Puts C declarations on a single line for `td`, dropping comments and
+/// preprocessor directives, which would otherwise swallow everything after
+/// them.
+fn strip_c(decl: &str) -> String {
+    let mut code = String::with_capacity(decl.len());
+    let mut chars = decl.chars().peekable();
+    let mut in_string = None;
+    while let Some(c) = chars.next() {
+        if let Some(quote) = in_string {
+            code.push(c);
+            if c == '\\' {
+                code.extend(chars.next());
+            } else if c == quote {
+                in_string = None;
+            }
+            continue;
+        }
+        match (c, chars.peek()) {
+            ('/', Some('/')) => {
+                chars.by_ref().take_while(|&c| c != '\n').for_each(drop);
+                code.push('\n');
+            }
+            ('/', Some('*')) => {
+                chars.next();
+                let mut prev = ' ';
+                for c in chars.by_ref() {
+                    if prev == '*' && c == '/' {
+                        break;
+                    }
+                    prev = c;
+                }
+                code.push(' ');
+            }
+            ('"', _) | ('\'', _) => {
+                in_string = Some(c);
+                code.push(c);
+            }
+            _ => code.push(c),
+        }
+    }
+    let mut lines = Vec::new();
+    let mut in_directive = false;
+    for line in code.lines() {
+        let trimmed = line.trim();
+        // directives continue on the next line after a trailing backslash
+        if in_directive || trimmed.starts_with('#') {
+            in_directive = trimmed.ends_with('\\');
+            continue;
+        }
+        lines.push(trimmed);
+    }
+    lines
+        .join(" ")
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Extracts the `pf` format from the output of `ts <name>`.
+fn struct_format(out: &str) -> Option<&str> {
+    let out = out.trim();
+    let format = out.strip_prefix("pf ").unwrap_or(out).trim();
+    (!format.is_empty()).then_some(format)
+}
+
+impl R2Pipe {
+    /// Parses C declarations and adds the resulting types (`td`).
+    /// Comments and preprocessor directives are ignored.
+    pub fn parse_c(&mut self, decl: &str) -> Result<()> {
+        self.call(&format!("td {}", strip_c(decl)))?;
+        Ok(())
+    }
+
+    /// Parses a C header file and adds the resulting types (`to`).
+    pub fn parse_c_file(&mut self, path: &str) -> Result<()> {
+        self.call(&format!("to {}", check_single_line(path)?))?;
+        Ok(())
+    }
+
+    pub fn structs(&mut self) -> Result<Vec<Struct>> {
+        let list = self.cmdj_or_null("tsj")?;
+        Ok(named_entries(list)
+            .into_iter()
+            .map(|(name, body)| {
+                let members = match body.get("members").cloned().unwrap_or(body) {
+                    Value::Array(members) => members
+                        .iter()
+                        .filter_map(Value::as_object)
+                        .map(|m| Member {
+                            name: str_field(m, "name"),
+                            kind: str_field(m, "type"),
+                            offset: m.get("offset").and_then(Value::as_u64),
+                        })
+                        .collect(),
+                    Value::Object(members) => members
+                        .iter()
+                        .filter(|(k, _)| k.as_str() != "name")
+                        .map(|(k, v)| Member {
+                            name: k.clone(),
+                            kind: v.as_str().unwrap_or_default().to_string(),
+                            offset: None,
+                        })
+                        .collect(),
+                    _ => Vec::new(),
+                };
+                Struct { name, members }
+            })
+            .collect())
+    }
+
+    pub fn enums(&mut self) -> Result<Vec<Enum>> {
+        let list = self.cmdj_or_null("tej")?;
+        Ok(named_entries(list)
+            .into_iter()
+            .map(|(name, body)| {
+                let values = body
+                    .get("values")
+                    .cloned()
+                    .unwrap_or(body)
+                    .as_object()
+                    .map(|vals| {
+                        vals.iter()
+                            .filter_map(|(k, v)| Some((k.clone(), v.as_i64()?)))
+                            .collect()
+                    })
+                    .unwrap_or_default();
+                Enum { name, values }
+            })
+            .collect())
+    }
+
+    pub fn typedefs(&mut self) -> Result<Vec<Typedef>> {
+        let list = self.cmdj_or_null("ttj")?;
+        Ok(named_entries(list)
+            .into_iter()
+            .map(|(name, body)| {
+                let kind = match &body {
+                    Value::String(s) => s.clone(),
+                    Value::Object(o) => str_field(o, "type"),
+                    _ => String::new(),
+                };
+                Typedef { name, kind }
+            })
+            .collect())
+    }
+
+    pub fn function_types(&mut self) -> Result<Vec<FunctionType>> {
+        let list = self.cmdj_or_null("tfj")?;
+        Ok(named_entries(list)
+            .into_iter()
+            .map(|(name, body)| {
+                let args = body
+                    .get("args")
+                    .and_then(Value::as_array)
+                    .map(|args| {
+                        args.iter()
+                            .filter_map(Value::as_object)
+                            .map(|a| Argument {
+                                name: str_field(a, "name"),
+                                kind: str_field(a, "type"),
+                            })
+                            .collect()
+                    })
+                    .unwrap_or_default();
+                let ret = body
+                    .as_object()
+                    .map(|o| str_field(o, "return"))
+                    .unwrap_or_default();
+                FunctionType { name, ret, args }
+            })
+            .collect())
+    }
+
+    /// Links the struct `name` to `addr` (`tl`) and decodes the memory
+    /// there according to its layout.
+    pub fn apply_struct(&mut self, name: &str, addr: u64) -> Result<Vec<Field>> {
+        let name = check_name(name)?;
+        self.cmd(&format!("tl {} = {:#x}", name, addr))?;
+        let out = self.cmd(&format!("ts {}", name))?;
+        match struct_format(&out) {
+            Some(format) => self.decode_spec(format, addr),
+            None => Err(Error::CommandFailed(format!("unknown struct {}", name))),
+        }
+    }
+
+    fn cmdj_or_null(&mut self, cmd: &str) -> Result<Value> {
+        match self.cmdj(cmd) {
+            Err(Error::EmptyResponse) => Ok(Value::Null),
+            res => res,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{flatten_fields, strip_c, struct_format};
+    use crate::R2Pipe;
+    use serde_json::json;
+
+    #[test]
+    fn fields_from_pfj() {
+        let pfj = json!([
+            {"name": "magic", "type": "d", "offset": 0, "value": 1179403647},
+            {"name": "hdr", "type": "?", "offset": 4, "value": [[
+                {"name": "size", "type": "w", "offset": 4, "value": 64}
+            ]]}
+        ]);
+        let fields = flatten_fields(pfj.as_array().unwrap());
+        assert_eq!(fields.len(), 2);
+        assert_eq!(fields[0].value, 1179403647);
+        assert_eq!(fields[1].fields[0].name, "size");
+        assert_eq!(fields[1].fields[0].offset, 4);
+    }
+
+    #[test]
+    fn c_declarations() {
+        let decl = r#"
+            #include <stdint.h>
+            #define MAGIC \
+                0x7f
+            // a point
+            struct point { int x; /* horizontal */ int y; };
+            struct name { char *s; }; // "not // a comment"
+            char *sep = "//";
+        "#;
+        assert_eq!(
+            strip_c(decl),
+            "struct point { int x; int y; }; struct name { char *s; }; char *sep = \"//\";"
+        );
+    }
+
+    #[test]
+    fn struct_formats() {
+        assert_eq!(
+            struct_format("pf ddw magic version flags\n"),
+            Some("ddw magic version flags")
+        );
+        assert_eq!(struct_format("\n"), None);
+    }
+
+    #[test]
+    fn declaration_order() {
+        let mut r2 = R2Pipe::from_fn(|cmd| {
+            match cmd {
+                "tsj" => r#"{"point": {"y": "int", "x": "int", "tag": "char"}}"#,
+                "tej" => r#"{"color": {"RED": 0, "GREEN": 1, "BLUE": 2}}"#,
+                _ => panic!("unexpected command {}", cmd),
+            }
+            .to_string()
+        });
+        let structs = r2.structs().unwrap();
+        let names: Vec<&str> = structs[0].members.iter().map(|m| m.name.as_str()).collect();
+        assert_eq!(names, ["y", "x", "tag"]);
+        let enums = r2.enums().unwrap();
+        let names: Vec<&str> = enums[0].values.iter().map(|(n, _)| n.as_str()).collect();
+        assert_eq!(names, ["RED", "GREEN", "BLUE"]);
+    }
+}
@@ -0,0 +1,75 @@
+//! Small helpers shared by the command wrappers.
+
+use crate::{Error, Result};
+
+/// Decodes a string of hex pairs such as the ones produced by `p8`.
+pub(crate) fn decode_hex(hex: &str) -> Option<Vec<u8>> {
+    let hex = hex.trim();
+    if hex.len() % 2 != 0 {
+        return None;
+    }
+    (0..hex.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(hex.get(i..i + 2)?, 16).ok())
+        .collect()
+}
+
+/// Parses a decimal or `0x`-prefixed hexadecimal number printed by r2.
+pub(crate) fn parse_u64(s: &str) -> Option<u64> {
+    let s = s.trim();
+    match s.strip_prefix("0x") {
+        Some(hex) => u64::from_str_radix(hex, 16).ok(),
+        None => s.parse().ok(),
+    }
+}
+
+/// Encodes bytes as a string of hex pairs, as accepted by `wx`.
+pub(crate) fn encode_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+/// Deserializes a hex string field into bytes.
+pub(crate) fn deserialize_hex<'de, D>(deserializer: D) -> std::result::Result<Vec<u8>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    use serde::de::{Deserialize, Error};
+    let hex = String::deserialize(deserializer)?;
+    decode_hex(&hex).ok_or_else(|| D::Error::custom(format!("invalid hex string: {}", hex)))
+}
+
+/// Ensures a user-supplied argument cannot spill into a second command.
+pub(crate) fn check_single_line(arg: &str) -> Result<&str> {
+    if arg.contains(['\n', '\r', '\0']) {
+        return Err(Error::InvalidArgument(format!(
+            "{:?} must fit on a single line",
+            arg
+        )));
+    }
+    Ok(arg)
+}
+
+/// Ensures a user-supplied flag, space or function name is made only of
+/// characters r2 accepts in names.
+pub(crate) fn check_name(name: &str) -> Result<&str> {
+    let valid = |c: char| c.is_ascii_alphanumeric() || "._:-$".contains(c);
+    if name.is_empty() || name.starts_with('-') || !name.chars().all(valid) {
+        return Err(Error::InvalidArgument(format!("invalid name {:?}", name)));
+    }
+    Ok(name)
+}
+
+#[cfg(test)]
+mod test {
+    use super::check_name;
+
+    #[test]
+    fn names() {
+        for name in ["my_flag", "sym.__libc_csu_init", "_ZN3FooC2Ev", "str.a:b$1"] {
+            assert_eq!(check_name(name).unwrap(), name);
+        }
+        for name in ["", "-all", "two words", "a;b", "a@0x10", "a\nb"] {
+            assert!(check_name(name).is_err(), "{:?} accepted", name);
+        }
+    }
+}
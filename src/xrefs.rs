@@ -0,0 +1,111 @@
+//! Cross-reference queries over `axtj` and `axfj`.
+
+use crate::{R2Pipe, Result};
+
+use serde::{Deserialize, Deserializer};
+use serde_derive::Deserialize;
+
+/// Kind of a cross reference.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum XrefKind {
+    Call,
+    Code,
+    Data,
+    String,
+    /// Any other kind reported by r2.
+    Other(String),
+}
+
+impl From<&str> for XrefKind {
+    fn from(kind: &str) -> XrefKind {
+        match kind {
+            "CALL" => XrefKind::Call,
+            "CODE" | "ICOD" => XrefKind::Code,
+            "DATA" => XrefKind::Data,
+            "STRN" | "STRING" => XrefKind::String,
+            other => XrefKind::Other(other.to_string()),
+        }
+    }
+}
+
+fn deserialize_kind<'de, D>(deserializer: D) -> std::result::Result<XrefKind, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    Ok(XrefKind::from(String::deserialize(deserializer)?.as_str()))
+}
+
+/// A reference from one address to another.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub struct Xref {
+    pub from: u64,
+    #[serde(default)]
+    pub to: u64,
+    #[serde(rename = "type", deserialize_with = "deserialize_kind")]
+    pub kind: XrefKind,
+    /// Instruction at the source of the reference.
+    #[serde(default)]
+    pub opcode: String,
+    /// Function containing the source of the reference, if any.
+    pub fcn_addr: Option<u64>,
+    pub fcn_name: Option<String>,
+}
+
+impl R2Pipe {
+    /// References pointing to `addr` (`axtj`).
+    pub fn xrefs_to(&mut self, addr: u64) -> Result<Vec<Xref>> {
+        let mut res: Vec<Xref> = self.cmdj_or_empty(&format!("axtj @ {:#x}", addr))?;
+        res.iter_mut().for_each(|x| x.to = addr);
+        Ok(res)
+    }
+
+    /// References originating from the instruction at `addr` (`axfj`).
+    pub fn xrefs_from(&mut self, addr: u64) -> Result<Vec<Xref>> {
+        self.cmdj_or_empty(&format!("axfj @ {:#x}", addr))
+    }
+
+    /// References pointing to each of `addrs`, queried in a single
+    /// round-trip. The result holds one list per address, in order.
+    pub fn xrefs_to_many(&mut self, addrs: &[u64]) -> Result<Vec<Vec<Xref>>> {
+        let cmds: Vec<String> = addrs.iter().map(|a| format!("axtj @ {:#x}", a)).collect();
+        let mut res: Vec<Vec<Xref>> = self.cmdj_batch(&cmds)?;
+        for (refs, &addr) in res.iter_mut().zip(addrs) {
+            refs.iter_mut().for_each(|x| x.to = addr);
+        }
+        Ok(res)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::XrefKind;
+    use crate::R2Pipe;
+    use serde_json::json;
+
+    #[test]
+    fn xrefs_to_many() {
+        let mut r2 = R2Pipe::from_fn(|cmd| {
+            let mut out = String::new();
+            for c in cmd.split(';') {
+                out += &match c {
+                    "axtj @ 0x10" => json!([
+                        {"from": 32, "type": "CALL", "opcode": "call 0x10",
+                         "fcn_addr": 32, "fcn_name": "main"}
+                    ])
+                    .to_string(),
+                    "axtj @ 0x20" => String::new(),
+                    "?e --" => "--".to_string(),
+                    other => panic!("unexpected command {}", other),
+                };
+                out.push('\n');
+            }
+            out
+        });
+        let refs = r2.xrefs_to_many(&[0x10, 0x20]).unwrap();
+        assert_eq!(refs.len(), 2);
+        assert_eq!(refs[0][0].from, 0x20);
+        assert_eq!(refs[0][0].to, 0x10);
+        assert_eq!(refs[0][0].kind, XrefKind::Call);
+        assert!(refs[1].is_empty());
+    }
+}
@@ -0,0 +1,212 @@
+//! Zignatures: function signatures used to recognize known code.
+//!
+//! Zignatures are generated from the analyzed functions (`zaf`, `zaF`),
+//! saved to and loaded from files (`zos`, `zo`) and matched either by
+//! searching the binary (`z/`) or by scoring functions against the loaded
+//! zignatures (`zb`).
+
+use crate::util::{check_name, check_single_line};
+use crate::{Error, R2Pipe, Result};
+
+use serde_derive::Deserialize;
+
+/// Graph metrics of a zignature.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize)]
+pub struct ZignatureGraph {
+    /// Cyclomatic complexity.
+    #[serde(default)]
+    pub cc: u64,
+    #[serde(default)]
+    pub nbbs: u64,
+    #[serde(default)]
+    pub edges: u64,
+    #[serde(default)]
+    pub ebbs: u64,
+    #[serde(default)]
+    pub bbsum: u64,
+}
+
+/// A zignature, as listed by `zj`.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub struct Zignature {
+    pub name: String,
+    /// Hex encoded bytes of the function, with `mask` applied.
+    #[serde(default)]
+    pub bytes: String,
+    #[serde(default)]
+    pub mask: String,
+    pub graph: Option<ZignatureGraph>,
+    /// Address of the function the zignature was generated from.
+    pub addr: Option<u64>,
+    /// Names of the functions referenced by the function.
+    #[serde(default)]
+    pub refs: Vec<String>,
+}
+
+/// A function matched by a zignature.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ZignatureMatch {
+    pub addr: u64,
+    pub zignature: String,
+    /// Similarity between `0.0` and `1.0`.
+    pub score: f64,
+}
+
+#[derive(Deserialize)]
+struct BestMatch {
+    name: String,
+    #[serde(alias = "similarity")]
+    score: f64,
+}
+
+/// Extracts the zignature name from a flag added by `z/`, named
+/// `<prefix>.<kind>.<zignature>_<n>`. Only the counter appended by `z/` is
+/// stripped, so names ending in `_<digits>` themselves are kept whole.
+fn zignature_from_flag<'a>(flag: &'a str, prefix: &str) -> Option<&'a str> {
+    let (_, name) = flag
+        .strip_prefix(prefix)?
+        .strip_prefix('.')?
+        .split_once('.')?;
+    let (name, n) = name.rsplit_once('_')?;
+    (!name.is_empty() && !n.is_empty() && n.bytes().all(|b| b.is_ascii_digit())).then_some(name)
+}
+
+impl R2Pipe {
+    /// Lists the loaded zignatures (`zj`).
+    pub fn zignatures(&mut self) -> Result<Vec<Zignature>> {
+        self.cmdj_or_empty("zj")
+    }
+
+    /// Generates a zignature for every analyzed function (`zaF`).
+    pub fn generate_zignatures(&mut self) -> Result<Vec<Zignature>> {
+        self.cmd("zaF")?;
+        self.zignatures()
+    }
+
+    /// Generates a zignature for each of the functions starting at `addrs`,
+    /// named after the function (`zaf`).
+    pub fn generate_zignatures_for(&mut self, addrs: &[u64]) -> Result<Vec<Zignature>> {
+        let mut names = Vec::with_capacity(addrs.len());
+        for &addr in addrs {
+            let f = self
+                .function_info(addr)?
+                .ok_or_else(|| Error::CommandFailed(format!("no function at {:#x}", addr)))?;
+            let name = check_name(&f.name)?;
+            self.cmd(&format!("zaf {} {}", name, name))?;
+            names.push(f.name);
+        }
+        let mut res = self.zignatures()?;
+        res.retain(|z| names.contains(&z.name));
+        Ok(res)
+    }
+
+    /// Removes all the loaded zignatures (`z-*`).
+    pub fn clear_zignatures(&mut self) -> Result<()> {
+        self.cmd("z-*")?;
+        Ok(())
+    }
+
+    /// Saves the loaded zignatures to `path` (`zos`).
+    pub fn save_zignatures(&mut self, path: &str) -> Result<()> {
+        self.call(&format!("zos {}", check_single_line(path)?))?;
+        Ok(())
+    }
+
+    /// Loads the zignatures stored in `path` (`zo`).
+    pub fn load_zignatures(&mut self, path: &str) -> Result<()> {
+        self.call(&format!("zo {}", check_single_line(path)?))?;
+        Ok(())
+    }
+
+    /// Searches the binary for the loaded zignatures (`z/`). Every hit is
+    /// an exact match of at least one of the zignature metrics and is
+    /// reported with a score of `1.0`.
+    pub fn search_zignatures(&mut self) -> Result<Vec<ZignatureMatch>> {
+        let prefix: String = self.config_get("zign.prefix")?;
+        self.cmd("z/")?;
+        let mut res: Vec<ZignatureMatch> = Vec::new();
+        for flag in self.flags(Some("sign"))? {
+            if let Some(name) = zignature_from_flag(&flag.name, &prefix) {
+                // a function matching several metrics gets one flag each
+                if !res
+                    .iter()
+                    .any(|m| m.addr == flag.offset && m.zignature == name)
+                {
+                    res.push(ZignatureMatch {
+                        addr: flag.offset,
+                        zignature: name.to_string(),
+                        score: 1.0,
+                    });
+                }
+            }
+        }
+        Ok(res)
+    }
+
+    /// Returns the `count` zignatures closest to the function at `addr`,
+    /// best first (`zbj`).
+    pub fn best_zignatures(&mut self, addr: u64, count: u64) -> Result<Vec<ZignatureMatch>> {
+        let best: Vec<BestMatch> = self.cmdj_or_empty(&format!("zbj {} @ {:#x}", count, addr))?;
+        Ok(best
+            .into_iter()
+            .map(|b| ZignatureMatch {
+                addr,
+                zignature: b.name,
+                score: b.score,
+            })
+            .collect())
+    }
+
+    /// Scores every analyzed function against the loaded zignatures and
+    /// returns the best match of each function scoring at least
+    /// `min_score`.
+    pub fn match_zignatures(&mut self, min_score: f64) -> Result<Vec<ZignatureMatch>> {
+        let addrs: Vec<u64> = self.functions()?.iter().map(|f| f.addr).collect();
+        let cmds: Vec<String> = addrs
+            .iter()
+            .map(|addr| format!("zbj 1 @ {:#x}", addr))
+            .collect();
+        let best: Vec<Vec<BestMatch>> = self.cmdj_batch(&cmds)?;
+        Ok(addrs
+            .into_iter()
+            .zip(best)
+            .filter_map(|(addr, best)| {
+                let b = best.into_iter().next()?;
+                (b.score >= min_score).then_some(ZignatureMatch {
+                    addr,
+                    zignature: b.name,
+                    score: b.score,
+                })
+            })
+            .collect())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{zignature_from_flag, BestMatch};
+    use serde_json::json;
+
+    #[test]
+    fn flag_names() {
+        let name = zignature_from_flag("sign.bytes.sym.main_0", "sign");
+        assert_eq!(name, Some("sym.main"));
+        let name = zignature_from_flag("sign.graph.entry0_12", "sign");
+        assert_eq!(name, Some("entry0"));
+        let name = zignature_from_flag("sign.bytes.sub_401000_1", "sign");
+        assert_eq!(name, Some("sub_401000"));
+        // not a hit of `z/`
+        assert_eq!(zignature_from_flag("sign.graph.entry0", "sign"), None);
+        assert_eq!(zignature_from_flag("sym.main", "sign"), None);
+    }
+
+    #[test]
+    fn best_from_zbj() {
+        let zbj = json!([{"name": "sym.foo", "similarity": 0.91, "byte": 0.9, "graph": 0.95},
+                         {"name": "sub_401000", "similarity": 0.5, "byte": 0.4, "graph": 0.6}]);
+        let best: Vec<BestMatch> = serde_json::from_value(zbj).unwrap();
+        assert_eq!(best[0].name, "sym.foo");
+        assert_eq!(best[0].score, 0.91);
+        assert_eq!(best[1].name, "sub_401000");
+    }
+}